@@ -2,9 +2,498 @@
 //!
 //! It offers functions to get and set string, integer, and array parameters,
 //! as well as to unset parameters, abstracting away the unsafe FFI calls to Zsh's C API.
+//! Single elements and slices can be accessed with [`ZshParameter::get_subscript`], and
+//! assignments to existing parameters can be observed with [`ZshParameter::watch`].
+mod subscript;
+mod tie;
+mod watch;
+pub use watch::*;
+
 use crate::bindings;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_int};
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// Errors that can occur while reading or modifying Zsh parameters.
+#[derive(Debug, Error)]
+pub enum ParamError {
+    /// The parameter name is not a valid identifier or contains a null byte.
+    #[error("Invalid parameter name '{0}'")]
+    InvalidName(String),
+    /// A value contains a null byte and cannot be passed to Zsh.
+    #[error("Invalid value for parameter '{0}'")]
+    InvalidValue(String),
+    /// The parameter is read-only (`typeset -r`).
+    #[error("Parameter '{0}' is read-only")]
+    ReadOnly(String),
+    /// The parameter exists but does not have the expected type.
+    #[error("Parameter '{0}' is not of type {1:?}")]
+    TypeMismatch(String, ParamKind),
+    /// Zsh failed to allocate memory.
+    #[error("zsh: out of memory")]
+    OutOfMemory,
+    /// The parameter does not exist or is unset.
+    #[error("Parameter '{0}' does not exist")]
+    NotFound(String),
+    /// Zsh refused the assignment for another reason.
+    #[error("Failed to set parameter '{0}'")]
+    Failed(String),
+}
+
+/// Converts `name` into a C string and checks that it may be assigned to.
+///
+/// # Errors
+/// - `ParamError::InvalidName`: `name` contains a null byte.
+/// - `ParamError::ReadOnly`: the parameter exists and is read-only.
+fn assignable_name(name: &str) -> Result<CString, ParamError> {
+    let c_name = CString::new(name).map_err(|_| ParamError::InvalidName(name.to_string()))?;
+    unsafe {
+        let pm = find_param(&c_name);
+        if !pm.is_null() && (*pm).node.flags & bindings::PM_READONLY as i32 != 0 {
+            return Err(ParamError::ReadOnly(name.to_string()));
+        }
+    }
+    Ok(c_name)
+}
+
+/// Builds the error for a Zsh setter (`setsparam` etc.) that returned NULL.
+fn set_failed(name: &str, c_name: &CStr) -> ParamError {
+    if unsafe { bindings::isident(c_name.as_ptr() as *mut c_char) } == 0 {
+        ParamError::InvalidName(name.to_string())
+    } else {
+        ParamError::Failed(name.to_string())
+    }
+}
+
+/// Zsh's `Meta` marker byte. A byte following it is stored XORed with 32.
+const META: u8 = 0x83;
+
+/// Copies a metafied Zsh string and decodes it into its raw bytes.
+///
+/// Unlike `bindings::unmetafy`, this does not modify the string in place,
+/// so it is safe to use on values still owned by Zsh (e.g. parameter values).
+///
+/// # Safety
+/// `ptr` must be a valid, NUL-terminated C string.
+pub(crate) unsafe fn unmetafy_bytes(ptr: *const c_char) -> Vec<u8> {
+    let raw = unsafe { CStr::from_ptr(ptr) }.to_bytes();
+    let mut out = Vec::with_capacity(raw.len());
+    let mut iter = raw.iter();
+    while let Some(&b) = iter.next() {
+        if b == META {
+            if let Some(&next) = iter.next() {
+                out.push(next ^ 32);
+            }
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// Metafies `bytes` into a string allocated on Zsh's heap (`META_HEAPDUP`).
+///
+/// This is the form Zsh expects from special parameter getters: the string is
+/// released automatically when the current heap is popped. Bytes after an
+/// interior NUL are dropped, as they cannot be represented in a C string.
+pub(crate) fn heap_metafy(bytes: &[u8]) -> *mut c_char {
+    let bytes = bytes.split(|&b| b == 0).next().unwrap_or_default();
+    let c_value = CString::new(bytes).unwrap_or_default();
+    unsafe {
+        bindings::metafy(
+            c_value.as_ptr() as *mut c_char,
+            bytes.len() as i32,
+            bindings::META_HEAPDUP as i32,
+        )
+    }
+}
+
+/// Collects a NULL-terminated array of metafied Zsh strings into unmetafied byte vectors.
+///
+/// # Safety
+/// `ptr` must be a valid, NULL-terminated array of valid C strings.
+unsafe fn collect_array(ptr: *mut *mut c_char) -> Vec<Vec<u8>> {
+    let mut values = Vec::new();
+    let mut curr = ptr;
+    unsafe {
+        while !(*curr).is_null() {
+            values.push(unmetafy_bytes(*curr));
+            curr = curr.add(1);
+        }
+    }
+    values
+}
+/// Builds a NULL-terminated, `zalloc`-allocated array of metafied strings.
+///
+/// Ownership of the returned array and its elements is meant to be passed to Zsh
+/// (e.g. `setaparam` / `sethparam`). On failure, everything allocated so far is freed.
+unsafe fn new_zarray<I, S>(name: &str, values: I) -> Result<*mut *mut c_char, ParamError>
+where
+    I: ExactSizeIterator<Item = S>,
+    S: AsRef<[u8]>,
+{
+    let count = values.len();
+    let array_size = (count + 1) * std::mem::size_of::<*mut c_char>();
+    unsafe {
+        let ptr_array = bindings::zalloc(array_size) as *mut *mut c_char;
+        if ptr_array.is_null() {
+            return Err(ParamError::OutOfMemory);
+        }
+        // Keep the array NULL-terminated at every step so `freearray` can clean it up.
+        *ptr_array = std::ptr::null_mut();
+
+        for (i, val) in values.enumerate() {
+            let p = match CString::new(val.as_ref()) {
+                Ok(c_val) => bindings::ztrdup_metafy(c_val.as_ptr()),
+                Err(_) => {
+                    bindings::freearray(ptr_array);
+                    return Err(ParamError::InvalidValue(name.to_string()));
+                }
+            };
+            if p.is_null() {
+                bindings::freearray(ptr_array);
+                return Err(ParamError::OutOfMemory);
+            }
+            *ptr_array.add(i) = p;
+            *ptr_array.add(i + 1) = std::ptr::null_mut();
+        }
+        Ok(ptr_array)
+    }
+}
+
+/// Looks up a parameter node in Zsh's parameter table.
+///
+/// Returns a null pointer if the parameter does not exist.
+///
+/// # Safety
+/// Must be called from the Zsh main thread while `paramtab` is valid.
+pub(crate) unsafe fn find_param(name: &CStr) -> bindings::Param {
+    unsafe {
+        let table = bindings::paramtab;
+        if table.is_null() {
+            return std::ptr::null_mut();
+        }
+        match (*table).getnode {
+            Some(getnode) => getnode(table, name.as_ptr()) as bindings::Param,
+            None => std::ptr::null_mut(),
+        }
+    }
+}
+
+/// Returns the hash table backing an associative array parameter,
+/// or a null pointer if `pm` is not a hash.
+///
+/// # Safety
+/// `pm` must be a valid parameter node.
+unsafe fn hash_table(pm: bindings::Param) -> bindings::HashTable {
+    unsafe {
+        if pm.is_null() || (*pm).node.flags & bindings::PM_HASHED as i32 == 0 {
+            return std::ptr::null_mut();
+        }
+        match (*(*pm).gsu.h).getfn {
+            Some(getfn) => getfn(pm),
+            None => std::ptr::null_mut(),
+        }
+    }
+}
+
+/// A numeric value as used by Zsh's arithmetic (`mnumber`).
+///
+/// Zsh numbers are either integers (`zlong`) or floating-point values (`double`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZshNumber {
+    /// An integer value (`MN_INTEGER`).
+    Int(bindings::zlong),
+    /// A floating-point value (`MN_FLOAT`).
+    Float(f64),
+}
+
+impl ZshNumber {
+    /// Converts a raw Zsh `mnumber` into a `ZshNumber`.
+    pub(crate) fn from_raw(n: bindings::mnumber) -> Self {
+        unsafe {
+            if n.type_ & bindings::MN_FLOAT as i32 != 0 {
+                ZshNumber::Float(n.u.d)
+            } else {
+                ZshNumber::Int(n.u.l)
+            }
+        }
+    }
+
+    /// Converts this value into a raw Zsh `mnumber`.
+    pub(crate) fn into_raw(self) -> bindings::mnumber {
+        let mut n: bindings::mnumber = unsafe { std::mem::zeroed() };
+        match self {
+            ZshNumber::Int(l) => {
+                n.u.l = l;
+                n.type_ = bindings::MN_INTEGER as i32;
+            }
+            ZshNumber::Float(d) => {
+                n.u.d = d;
+                n.type_ = bindings::MN_FLOAT as i32;
+            }
+        }
+        n
+    }
+
+    /// Returns the value as a floating-point number, converting integers.
+    pub fn as_f64(self) -> f64 {
+        match self {
+            ZshNumber::Int(l) => l as f64,
+            ZshNumber::Float(d) => d,
+        }
+    }
+}
+
+impl From<bindings::zlong> for ZshNumber {
+    fn from(value: bindings::zlong) -> Self {
+        ZshNumber::Int(value)
+    }
+}
+
+impl From<f64> for ZshNumber {
+    fn from(value: f64) -> Self {
+        ZshNumber::Float(value)
+    }
+}
+
+/// The type of a Zsh parameter, as given by the `PM_TYPE` bits of its flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    /// A string parameter (`PM_SCALAR`).
+    Scalar,
+    /// An integer parameter (`PM_INTEGER`, `typeset -i`).
+    Integer,
+    /// A floating-point parameter (`PM_EFLOAT` / `PM_FFLOAT`, `typeset -E` / `typeset -F`).
+    Float,
+    /// An array parameter (`PM_ARRAY`).
+    Array,
+    /// An associative array parameter (`PM_HASHED`).
+    Assoc,
+}
+
+impl ParamKind {
+    /// Determines the parameter type from raw `PM_*` flags.
+    pub fn from_flags(flags: i32) -> Self {
+        let flags = flags as u32;
+        if flags & bindings::PM_HASHED != 0 {
+            ParamKind::Assoc
+        } else if flags & bindings::PM_ARRAY != 0 {
+            ParamKind::Array
+        } else if flags & (bindings::PM_EFLOAT | bindings::PM_FFLOAT) != 0 {
+            ParamKind::Float
+        } else if flags & bindings::PM_INTEGER != 0 {
+            ParamKind::Integer
+        } else {
+            ParamKind::Scalar
+        }
+    }
+}
+
+/// The attribute flags of a Zsh parameter (the `PM_*` bits of `param.node.flags`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamFlags(i32);
+
+impl ParamFlags {
+    /// Wraps raw `PM_*` flags.
+    pub fn from_bits(bits: i32) -> Self {
+        ParamFlags(bits)
+    }
+
+    /// Returns the raw `PM_*` flags.
+    pub fn bits(self) -> i32 {
+        self.0
+    }
+
+    fn has(self, flag: u32) -> bool {
+        self.0 as u32 & flag != 0
+    }
+
+    /// Returns the parameter type encoded in these flags.
+    pub fn kind(self) -> ParamKind {
+        ParamKind::from_flags(self.0)
+    }
+
+    /// `typeset -r`: the parameter is read-only.
+    pub fn is_readonly(self) -> bool {
+        self.has(bindings::PM_READONLY)
+    }
+
+    /// `typeset -x`: the parameter is exported to the environment.
+    pub fn is_exported(self) -> bool {
+        self.has(bindings::PM_EXPORTED)
+    }
+
+    /// `typeset -T`: the parameter is tied to another one.
+    pub fn is_tied(self) -> bool {
+        self.has(bindings::PM_TIED)
+    }
+
+    /// `typeset -l`: the value is converted to lower case.
+    pub fn is_lower(self) -> bool {
+        self.has(bindings::PM_LOWER)
+    }
+
+    /// `typeset -u`: the value is converted to upper case.
+    pub fn is_upper(self) -> bool {
+        self.has(bindings::PM_UPPER)
+    }
+
+    /// `typeset -U`: duplicate array elements are removed.
+    pub fn is_unique(self) -> bool {
+        self.has(bindings::PM_UNIQUE)
+    }
+
+    /// The parameter is local to a function (`local` / `typeset` inside a function).
+    pub fn is_local(self) -> bool {
+        self.has(bindings::PM_LOCAL)
+    }
+
+    /// The parameter is special (provided by the shell or a module).
+    pub fn is_special(self) -> bool {
+        self.has(bindings::PM_SPECIAL)
+    }
+
+    /// The parameter is declared but has no value.
+    pub fn is_unset(self) -> bool {
+        self.has(bindings::PM_UNSET)
+    }
+}
+
+/// A typed snapshot of the value of a Zsh parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZshValue {
+    /// The value of a string parameter.
+    Scalar(String),
+    /// The value of an integer parameter.
+    Integer(bindings::zlong),
+    /// The value of a floating-point parameter.
+    Float(f64),
+    /// The elements of an array parameter.
+    Array(Vec<String>),
+    /// The contents of an associative array parameter.
+    Assoc(HashMap<String, String>),
+}
+
+impl ZshValue {
+    /// Returns the type of this value.
+    pub fn kind(&self) -> ParamKind {
+        match self {
+            ZshValue::Scalar(_) => ParamKind::Scalar,
+            ZshValue::Integer(_) => ParamKind::Integer,
+            ZshValue::Float(_) => ParamKind::Float,
+            ZshValue::Array(_) => ParamKind::Array,
+            ZshValue::Assoc(_) => ParamKind::Assoc,
+        }
+    }
+}
+
+/// The scope in which [`ZshParameter::declare`] creates a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamScope {
+    /// Local to the currently executing shell function, like `local` / `typeset`.
+    ///
+    /// The parameter is removed (and any outer one restored) when the function returns.
+    /// Outside of any function this behaves like `Global`.
+    Local,
+    /// Global, like `typeset -g`: an existing visible parameter is reused,
+    /// otherwise a new global parameter is created.
+    Global,
+}
+
+impl ParamKind {
+    /// Returns the `PM_*` type flag used to create a parameter of this type.
+    fn create_flags(self) -> i32 {
+        (match self {
+            ParamKind::Scalar => bindings::PM_SCALAR,
+            ParamKind::Integer => bindings::PM_INTEGER,
+            ParamKind::Float => bindings::PM_FFLOAT,
+            ParamKind::Array => bindings::PM_ARRAY,
+            ParamKind::Assoc => bindings::PM_HASHED,
+        }) as i32
+    }
+}
+
+/// Summary information about a parameter, as produced by [`ZshParameter::iter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamInfo {
+    /// The (unmetafied) name of the parameter.
+    pub name: String,
+    /// The type of the parameter.
+    pub kind: ParamKind,
+    /// The attribute flags of the parameter.
+    pub flags: ParamFlags,
+}
+
+/// Selects which parameters [`ZshParameter::iter_filtered`] yields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamFilter {
+    /// Skip parameters that are declared but unset (`PM_UNSET`).
+    pub skip_unset: bool,
+    /// Skip special parameters provided by the shell or modules (`PM_SPECIAL`).
+    pub skip_special: bool,
+}
+
+impl Default for ParamFilter {
+    /// Skips unset parameters but keeps special ones, like a plain `typeset`.
+    fn default() -> Self {
+        Self {
+            skip_unset: true,
+            skip_special: false,
+        }
+    }
+}
+
+impl ParamFilter {
+    /// Returns whether a parameter with the given flags is selected by this filter.
+    pub fn matches(self, flags: ParamFlags) -> bool {
+        !(self.skip_unset && flags.is_unset() || self.skip_special && flags.is_special())
+    }
+}
+
+/// Collects the nodes visited by `scanhashtable`, which has no user data pointer.
+static SCAN_RESULTS: Mutex<Vec<ParamInfo>> = Mutex::new(Vec::new());
+
+/// `ScanFunc` callback that records each visited parameter in `SCAN_RESULTS`.
+unsafe extern "C" fn collect_param(node: bindings::HashNode, _flags: c_int) {
+    if node.is_null() {
+        return;
+    }
+    let (name, flags) = unsafe {
+        if (*node).nam.is_null() {
+            return;
+        }
+        (
+            unmetafy_bytes((*node).nam),
+            ParamFlags::from_bits((*node).flags),
+        )
+    };
+    if let Ok(mut results) = SCAN_RESULTS.lock() {
+        results.push(ParamInfo {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            kind: flags.kind(),
+            flags,
+        });
+    }
+}
+
+/// Runs `scan` with `collect_param` as the callback and returns the collected parameters.
+fn collect_scan(scan: impl FnOnce()) -> Vec<ParamInfo> {
+    if let Ok(mut results) = SCAN_RESULTS.lock() {
+        results.clear();
+    }
+    scan();
+    let mut params = SCAN_RESULTS
+        .lock()
+        .map(|mut results| std::mem::take(&mut *results))
+        .unwrap_or_default();
+    // Zsh's sorted scan allocates on its heap, so sort on the Rust side instead.
+    params.sort_by(|a, b| a.name.cmp(&b.name));
+    params
+}
+
 /// `ZshParameter` provides a safe interface for accessing and modifying Zsh shell parameters.
 pub struct ZshParameter;
 
@@ -35,53 +524,130 @@ impl ZshParameter {
     /// This function metafies the string value, making it safe for Zsh,
     /// and transfers ownership of the allocated memory to Zsh.
     ///
-    /// Returns `Ok(())` on success, or an `Err` with a static string if the
-    /// name or value is invalid, or if setting the parameter fails.
-    pub fn set_str(name: &str, value: &str) -> Result<(), &'static str> {
-        let c_name = CString::new(name).map_err(|_| "Invalid name")?;
-        let c_value = CString::new(value).map_err(|_| "Invalid value")?;
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` is not a valid parameter name.
+    /// - `ParamError::InvalidValue`: `value` contains a null byte.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::OutOfMemory`: Zsh failed to allocate the value.
+    /// - `ParamError::Failed`: Zsh refused the assignment.
+    pub fn set_str(name: &str, value: &str) -> Result<(), ParamError> {
+        let c_name = assignable_name(name)?;
+        let c_value =
+            CString::new(value).map_err(|_| ParamError::InvalidValue(name.to_string()))?;
 
         unsafe {
             // `ztrdup_metafy` duplicates the string and simultaneously escapes special characters
             // (metafies it) for safe use within Zsh. This is essential for multi-byte
             // characters and other special bytes. Zsh will manage the memory of the returned pointer.
             let ptr = bindings::ztrdup_metafy(c_value.as_ptr());
+            if ptr.is_null() {
+                return Err(ParamError::OutOfMemory);
+            }
 
             // Calls Zsh's `setsparam` to set the string parameter.
+            // On failure, Zsh frees `ptr` itself.
             let res = bindings::setsparam(c_name.as_ptr() as *mut c_char, ptr);
             if !res.is_null() {
                 Ok(())
             } else {
-                // If `setsparam` fails, Zsh does not take ownership of `ptr`, so we should free it.
-                // However, `setsparam` failure is rare. For now, we accept the small memory leak
-                // in this failure case, consistent with the existing `set_array` error handling.
-                Err("Failed to set string parameter")
+                Err(set_failed(name, &c_name))
             }
         }
     }
 
     /// Retrieves the value of an integer parameter from Zsh.
     ///
-    /// Returns the integer value. If the parameter does not exist or cannot be
-    /// converted to an integer, Zsh's internal `getiparam` behavior (usually 0) applies.
-    pub fn get_int(name: &str) -> bindings::zlong {
-        let c_name = CString::new(name).unwrap_or_default();
-        unsafe { bindings::getiparam(c_name.as_ptr() as *mut c_char) }
+    /// Non-integer values are evaluated arithmetically, as Zsh's `getiparam` does.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` contains a null byte.
+    /// - `ParamError::NotFound`: the parameter does not exist or is unset.
+    pub fn get_int(name: &str) -> Result<bindings::zlong, ParamError> {
+        let c_name = CString::new(name).map_err(|_| ParamError::InvalidName(name.to_string()))?;
+        unsafe {
+            // `getiparam` reports 0 for missing parameters, so check existence first.
+            let pm = find_param(&c_name);
+            if pm.is_null() || (*pm).node.flags & bindings::PM_UNSET as i32 != 0 {
+                return Err(ParamError::NotFound(name.to_string()));
+            }
+            Ok(bindings::getiparam(c_name.as_ptr() as *mut c_char))
+        }
     }
 
     /// Sets the value of an integer parameter in Zsh.
     ///
-    /// Returns `Ok(())` on success, or an `Err` with a static string if the
-    /// name is invalid or setting the parameter fails.
-    pub fn set_int(name: &str, value: bindings::zlong) -> Result<(), &'static str> {
-        let c_name = CString::new(name).map_err(|_| "Invalid name")?;
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` is not a valid parameter name.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::Failed`: Zsh refused the assignment.
+    pub fn set_int(name: &str, value: bindings::zlong) -> Result<(), ParamError> {
+        let c_name = assignable_name(name)?;
         unsafe {
             // Calls Zsh's `setiparam` to set the integer parameter.
             let res = bindings::setiparam(c_name.as_ptr() as *mut c_char, value);
             if !res.is_null() {
                 Ok(())
             } else {
-                Err("Failed to set integer parameter")
+                Err(set_failed(name, &c_name))
+            }
+        }
+    }
+
+    /// Retrieves the numeric value of a parameter from Zsh.
+    ///
+    /// Integer parameters yield `ZshNumber::Int`, and floating-point parameters
+    /// (`typeset -F` / `typeset -E`) yield `ZshNumber::Float`. Scalars are
+    /// evaluated arithmetically, as Zsh does.
+    ///
+    /// Returns `None` if the parameter does not exist or is unset.
+    pub fn get_number(name: &str) -> Option<ZshNumber> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            // `getnparam` reports 0 for missing parameters, so check existence first.
+            let pm = find_param(&c_name);
+            if pm.is_null() || (*pm).node.flags & bindings::PM_UNSET as i32 != 0 {
+                return None;
+            }
+            Some(ZshNumber::from_raw(bindings::getnparam(
+                c_name.as_ptr() as *mut c_char
+            )))
+        }
+    }
+
+    /// Retrieves the value of a parameter from Zsh as a floating-point number.
+    ///
+    /// Integer values are converted to `f64`.
+    /// Returns `None` if the parameter does not exist or is unset.
+    pub fn get_float(name: &str) -> Option<f64> {
+        Self::get_number(name).map(ZshNumber::as_f64)
+    }
+
+    /// Sets the value of a floating-point parameter in Zsh.
+    ///
+    /// A new parameter is created as `typeset -F`. Assigning to an existing
+    /// integer parameter follows Zsh's usual arithmetic conversion.
+    ///
+    /// See [`ZshParameter::set_number`] for the possible errors.
+    pub fn set_float(name: &str, value: f64) -> Result<(), ParamError> {
+        Self::set_number(name, ZshNumber::Float(value))
+    }
+
+    /// Sets the numeric value of a parameter in Zsh.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` is not a valid parameter name.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::Failed`: Zsh refused the assignment.
+    pub fn set_number(name: &str, value: ZshNumber) -> Result<(), ParamError> {
+        let c_name = assignable_name(name)?;
+        unsafe {
+            // Calls Zsh's `setnparam`, which creates `PM_FFLOAT` or `PM_INTEGER`
+            // parameters depending on the type of the number.
+            let res = bindings::setnparam(c_name.as_ptr() as *mut c_char, value.into_raw());
+            if !res.is_null() {
+                Ok(())
+            } else {
+                Err(set_failed(name, &c_name))
             }
         }
     }
@@ -91,53 +657,527 @@ impl ZshParameter {
     /// The array elements are metafied and the array itself is allocated using
     /// Zsh's memory functions, ensuring proper memory management within Zsh.
     ///
-    /// Returns `Ok(())` on success, or an `Err` with a static string if the
-    /// name is invalid or setting the parameter fails.
-    pub fn set_array(name: &str, values: Vec<&str>) -> Result<(), &'static str> {
-        let c_name = CString::new(name).map_err(|_| "Invalid name")?;
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` is not a valid parameter name.
+    /// - `ParamError::InvalidValue`: an element contains a null byte.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::OutOfMemory`: Zsh failed to allocate the array.
+    /// - `ParamError::Failed`: Zsh refused the assignment.
+    pub fn set_array(name: &str, values: Vec<&str>) -> Result<(), ParamError> {
+        let c_name = assignable_name(name)?;
 
         unsafe {
-            // 1. Allocate memory for the array of pointers using `zalloc`.
-            // The size is (number of elements + 1 for NULL terminator) * size of a pointer.
-            let count = values.len();
-            let array_size = (count + 1) * std::mem::size_of::<*mut c_char>();
-            let ptr_array = bindings::zalloc(array_size) as *mut *mut c_char;
+            // Allocate a NULL-terminated array of metafied elements with `zalloc`.
+            let ptr_array = new_zarray(name, values.into_iter())?;
 
-            if ptr_array.is_null() {
-                return Err("zsh: out of memory");
+            // Call Zsh's `setaparam` to set the array parameter.
+            // Zsh takes ownership of `ptr_array` and its contents, and frees them on failure.
+            let res = bindings::setaparam(c_name.as_ptr() as *mut c_char, ptr_array);
+
+            if !res.is_null() {
+                Ok(())
+            } else {
+                Err(set_failed(name, &c_name))
             }
+        }
+    }
 
-            // 2. Metafy each string and store the pointer in the allocated array.
-            for (i, val) in values.into_iter().enumerate() {
-                let c_val = CString::new(val).map_err(|_| "Invalid value in array")?;
-                // `ztrdup_metafy` ensures each element is safe for Zsh.
-                let p = bindings::ztrdup_metafy(c_val.as_ptr());
-                if p.is_null() {
-                    // In case of memory failure, we should ideally free the previously
-                    // allocated strings and the array itself. This is complex.
-                    // For now, we return an error.
-                    return Err("zsh: out of memory during array value allocation");
-                }
-                *ptr_array.add(i) = p;
+    /// Retrieves the elements of an array parameter from Zsh.
+    ///
+    /// If `name` is an associative array, its values are returned instead.
+    /// Each element is unmetafied; bytes that are not valid UTF-8 are replaced
+    /// lossily. Use [`ZshParameter::get_array_bytes`] to get the raw bytes.
+    ///
+    /// Returns `None` if the parameter does not exist or is not an array.
+    pub fn get_array(name: &str) -> Option<Vec<String>> {
+        Self::get_array_bytes(name).map(|values| {
+            values
+                .into_iter()
+                .map(|v| String::from_utf8_lossy(&v).into_owned())
+                .collect()
+        })
+    }
+
+    /// Retrieves the elements of an array parameter from Zsh as raw bytes.
+    ///
+    /// This behaves like [`ZshParameter::get_array`], but keeps elements that
+    /// are not valid UTF-8 (e.g. latin-1 file names) intact.
+    pub fn get_array_bytes(name: &str) -> Option<Vec<Vec<u8>>> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            // `getaparam` only returns plain arrays; fall back to `gethparam`,
+            // which returns the values of an associative array.
+            let mut ptr = bindings::getaparam(c_name.as_ptr() as *mut c_char);
+            if ptr.is_null() {
+                ptr = bindings::gethparam(c_name.as_ptr() as *mut c_char);
+            }
+            if ptr.is_null() {
+                None
+            } else {
+                Some(collect_array(ptr))
             }
+        }
+    }
 
-            // 3. NULL-terminate the array, as required by Zsh.
-            *ptr_array.add(count) = std::ptr::null_mut();
+    /// Retrieves the contents of an associative array parameter from Zsh.
+    ///
+    /// Keys and values are unmetafied and converted lossily to UTF-8.
+    ///
+    /// Returns `None` if the parameter does not exist or is not an associative array.
+    pub fn get_assoc(name: &str) -> Option<HashMap<String, String>> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            // `gethkparam` and `gethparam` scan the same table, so keys and values line up.
+            let keys = bindings::gethkparam(c_name.as_ptr() as *mut c_char);
+            let vals = bindings::gethparam(c_name.as_ptr() as *mut c_char);
+            if keys.is_null() || vals.is_null() {
+                return None;
+            }
+            let keys = collect_array(keys);
+            let vals = collect_array(vals);
+            Some(
+                keys.into_iter()
+                    .zip(vals)
+                    .map(|(k, v)| {
+                        (
+                            String::from_utf8_lossy(&k).into_owned(),
+                            String::from_utf8_lossy(&v).into_owned(),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+    }
 
-            // 4. Call Zsh's `setaparam` to set the array parameter.
-            // Zsh takes ownership of `ptr_array` and its contents.
-            let res = bindings::setaparam(c_name.as_ptr() as *mut c_char, ptr_array);
+    /// Replaces the contents of an associative array parameter in Zsh.
+    ///
+    /// The parameter is created if it does not exist. Keys and values are metafied
+    /// and passed to Zsh's `sethparam`, which takes ownership of them.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` is not a valid parameter name.
+    /// - `ParamError::InvalidValue`: a key or value contains a null byte.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::OutOfMemory`: Zsh failed to allocate the entries.
+    /// - `ParamError::Failed`: Zsh refused the assignment.
+    pub fn set_assoc(name: &str, values: &HashMap<String, String>) -> Result<(), ParamError> {
+        let c_name = assignable_name(name)?;
+        let flat: Vec<&str> = values
+            .iter()
+            .flat_map(|(k, v)| [k.as_str(), v.as_str()])
+            .collect();
 
+        unsafe {
+            // `sethparam` expects a flat `key, value, key, value, ...` array.
+            let ptr_array = new_zarray(name, flat.into_iter())?;
+            let res = bindings::sethparam(c_name.as_ptr() as *mut c_char, ptr_array);
             if !res.is_null() {
                 Ok(())
             } else {
-                // In case of failure, normally we should free the allocated memory.
-                // However, `setaparam` rarely fails, and Zsh often handles cleanup.
-                Err("Failed to set array parameter")
+                Err(set_failed(name, &c_name))
+            }
+        }
+    }
+
+    /// Retrieves a single element `name[key]` of an associative array parameter.
+    ///
+    /// Returns `None` if the parameter is not an associative array or the key is not set.
+    pub fn get_assoc_key(name: &str, key: &str) -> Option<String> {
+        let c_name = CString::new(name).ok()?;
+        let c_key = CString::new(key).ok()?;
+        unsafe {
+            let table = hash_table(find_param(&c_name));
+            if table.is_null() {
+                return None;
+            }
+            // Keys are stored metafied in the element table.
+            let meta_key = bindings::ztrdup_metafy(c_key.as_ptr());
+            let elem = (*table).getnode.and_then(|getnode| {
+                let pm = getnode(table, meta_key) as bindings::Param;
+                (!pm.is_null()).then_some(pm)
+            });
+            bindings::zsfree(meta_key);
+
+            let elem = elem?;
+            if (*elem).node.flags & bindings::PM_UNSET as i32 != 0 {
+                return None;
+            }
+            let getfn = (*(*elem).gsu.s).getfn?;
+            let value = getfn(elem);
+            if value.is_null() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(&unmetafy_bytes(value)).into_owned())
+            }
+        }
+    }
+
+    /// Sets a single element `name[key]` of an associative array parameter.
+    ///
+    /// The element is created if it does not exist, mirroring Zsh's own
+    /// subscript assignment. The parameter itself must already be an associative array.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` contains a null byte.
+    /// - `ParamError::InvalidValue`: `key` or `value` contains a null byte.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::NotFound`: the parameter does not exist.
+    /// - `ParamError::TypeMismatch`: the parameter is not an associative array.
+    /// - `ParamError::Failed`: the element could not be created or assigned.
+    pub fn set_assoc_key(name: &str, key: &str, value: &str) -> Result<(), ParamError> {
+        let c_name = assignable_name(name)?;
+        let invalid_value = |_| ParamError::InvalidValue(name.to_string());
+        let c_key = CString::new(key).map_err(invalid_value)?;
+        let c_value = CString::new(value).map_err(invalid_value)?;
+
+        unsafe {
+            let pm = find_param(&c_name);
+            if pm.is_null() {
+                return Err(ParamError::NotFound(name.to_string()));
+            }
+            let table = hash_table(pm);
+            if table.is_null() {
+                return Err(ParamError::TypeMismatch(name.to_string(), ParamKind::Assoc));
+            }
+
+            let meta_key = bindings::ztrdup_metafy(c_key.as_ptr());
+            let mut elem = match (*table).getnode {
+                Some(getnode) => getnode(table, meta_key) as bindings::Param,
+                None => std::ptr::null_mut(),
+            };
+            if elem.is_null() {
+                // Zsh creates missing elements by temporarily swapping `paramtab`
+                // for the element table and calling `createparam`.
+                let saved = bindings::paramtab;
+                bindings::paramtab = table;
+                elem = bindings::createparam(
+                    meta_key,
+                    (bindings::PM_SCALAR | bindings::PM_UNSET) as i32,
+                );
+                bindings::paramtab = saved;
+            }
+            bindings::zsfree(meta_key);
+
+            if elem.is_null() {
+                return Err(ParamError::Failed(name.to_string()));
+            }
+            let setfn = (*(*elem).gsu.s)
+                .setfn
+                .ok_or_else(|| ParamError::Failed(name.to_string()))?;
+            (*elem).node.flags &= !(bindings::PM_UNSET as i32);
+            setfn(elem, bindings::ztrdup_metafy(c_value.as_ptr()));
+            Ok(())
+        }
+    }
+
+    /// Unsets a single element `name[key]` of an associative array parameter.
+    ///
+    /// If the parameter or the key does not exist, this function does nothing.
+    pub fn unset_assoc_key(name: &str, key: &str) {
+        let (Ok(c_name), Ok(c_key)) = (CString::new(name), CString::new(key)) else {
+            return;
+        };
+        unsafe {
+            let table = hash_table(find_param(&c_name));
+            if table.is_null() {
+                return;
+            }
+            let meta_key = bindings::ztrdup_metafy(c_key.as_ptr());
+            if let Some(getnode) = (*table).getnode {
+                let elem = getnode(table, meta_key) as bindings::Param;
+                if !elem.is_null() {
+                    // As in `unset 'name[key]'`, `unsetparam_pm` operates on `paramtab`,
+                    // so point it at the element table for the duration of the call.
+                    let saved = bindings::paramtab;
+                    bindings::paramtab = table;
+                    bindings::unsetparam_pm(elem, 0, 1);
+                    bindings::paramtab = saved;
+                }
+            }
+            bindings::zsfree(meta_key);
+        }
+    }
+
+    /// Retrieves the attribute flags of a parameter.
+    ///
+    /// Returns `None` if the parameter does not exist or is unset.
+    pub fn flags(name: &str) -> Option<ParamFlags> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            let pm = find_param(&c_name);
+            if pm.is_null() {
+                return None;
+            }
+            let flags = ParamFlags::from_bits((*pm).node.flags);
+            if flags.is_unset() { None } else { Some(flags) }
+        }
+    }
+
+    /// Retrieves the type of a parameter by inspecting its `PM_TYPE` flags.
+    ///
+    /// Returns `None` if the parameter does not exist or is unset.
+    pub fn kind(name: &str) -> Option<ParamKind> {
+        Self::flags(name).map(ParamFlags::kind)
+    }
+
+    /// Retrieves the value of a parameter as a [`ZshValue`] matching its type.
+    ///
+    /// Returns `None` if the parameter does not exist or is unset.
+    pub fn get(name: &str) -> Option<ZshValue> {
+        match Self::kind(name)? {
+            ParamKind::Scalar => {
+                let c_name = CString::new(name).ok()?;
+                unsafe {
+                    let ptr = bindings::getsparam(c_name.as_ptr() as *mut c_char);
+                    if ptr.is_null() {
+                        return None;
+                    }
+                    let bytes = unmetafy_bytes(ptr);
+                    Some(ZshValue::Scalar(
+                        String::from_utf8_lossy(&bytes).into_owned(),
+                    ))
+                }
+            }
+            ParamKind::Integer => match Self::get_number(name)? {
+                ZshNumber::Int(l) => Some(ZshValue::Integer(l)),
+                ZshNumber::Float(d) => Some(ZshValue::Float(d)),
+            },
+            ParamKind::Float => Self::get_float(name).map(ZshValue::Float),
+            ParamKind::Array => Self::get_array(name).map(ZshValue::Array),
+            ParamKind::Assoc => Self::get_assoc(name).map(ZshValue::Assoc),
+        }
+    }
+
+    /// Returns the current function nesting level (Zsh's `locallevel`).
+    ///
+    /// This is `0` at the top level and increases by one for each active shell function.
+    pub fn local_level() -> i32 {
+        unsafe { bindings::locallevel }
+    }
+
+    /// Declares a parameter of type `kind` in the given scope, like `local` or `typeset -g`.
+    ///
+    /// The new parameter is initialised with an empty value of its type. If a matching
+    /// parameter is already visible in the requested scope, it is left untouched.
+    /// A parameter that was declared but unset there (e.g. after `unset name`) is
+    /// reused like `typeset` does: an ordinary one is recreated with the requested
+    /// type and an empty value, and a special one is marked as set again.
+    /// Use the `set_*` functions afterwards to assign a value; they always target
+    /// the innermost visible parameter, i.e. the one declared here.
+    ///
+    /// ```ignore
+    /// // Inside a builtin called from a shell function:
+    /// ZshParameter::declare("reply", ParamKind::Scalar, ParamScope::Local)?;
+    /// ZshParameter::set_str("reply", "done")?;
+    /// ```
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` is not a valid parameter name.
+    /// - `ParamError::ReadOnly`: a local cannot be created over a read-only parameter.
+    /// - `ParamError::TypeMismatch`: a parameter of another type already exists in the requested scope.
+    /// - `ParamError::Failed`: Zsh refused to create the parameter.
+    pub fn declare(name: &str, kind: ParamKind, scope: ParamScope) -> Result<(), ParamError> {
+        let c_name = CString::new(name).map_err(|_| ParamError::InvalidName(name.to_string()))?;
+        unsafe {
+            if bindings::isident(c_name.as_ptr() as *mut c_char) == 0 {
+                return Err(ParamError::InvalidName(name.to_string()));
+            }
+
+            let level = bindings::locallevel;
+            let local = scope == ParamScope::Local && level > 0;
+            let existing = find_param(&c_name);
+            if !existing.is_null() {
+                let same_level = (*existing).level == level;
+                let flags = (*existing).node.flags;
+                let unset = flags & bindings::PM_UNSET as i32 != 0;
+                let special = flags & bindings::PM_SPECIAL as i32 != 0;
+                // An unset ordinary parameter is replaced below: `createparam` reuses
+                // its node for the new type, as `typeset` does.
+                if (!local || same_level) && (!unset || special) {
+                    // `typeset -g` and repeated `local` reuse the visible parameter,
+                    // as long as it already has the requested type.
+                    if ParamKind::from_flags(flags) != kind {
+                        return Err(ParamError::TypeMismatch(name.to_string(), kind));
+                    }
+                    // An unset special parameter keeps its GSU and is simply set again,
+                    // which is what `createparam` would do for it.
+                    (*existing).node.flags &= !(bindings::PM_UNSET as i32);
+                    return Ok(());
+                }
+            }
+
+            let mut flags = kind.create_flags();
+            if local {
+                flags |= bindings::PM_LOCAL as i32;
+            }
+            // `createparam` hides any outer parameter of the same name when `PM_LOCAL`
+            // is given; the caller is responsible for setting the level, as `typeset` does.
+            let pm = bindings::createparam(c_name.as_ptr() as *mut c_char, flags);
+            if pm.is_null() {
+                return Err(
+                    if !existing.is_null()
+                        && (*existing).node.flags & bindings::PM_READONLY as i32 != 0
+                    {
+                        ParamError::ReadOnly(name.to_string())
+                    } else {
+                        ParamError::Failed(name.to_string())
+                    },
+                );
+            }
+            if local {
+                (*pm).level = level;
+            }
+
+            // Give the parameter an empty value of its type, as `typeset name` does.
+            match kind {
+                ParamKind::Scalar => {
+                    if let Some(setfn) = (*(*pm).gsu.s).setfn {
+                        setfn(pm, bindings::ztrdup(c"".as_ptr()));
+                    }
+                }
+                ParamKind::Integer => {
+                    if let Some(setfn) = (*(*pm).gsu.i).setfn {
+                        setfn(pm, 0);
+                    }
+                }
+                ParamKind::Float => {
+                    if let Some(setfn) = (*(*pm).gsu.f).setfn {
+                        setfn(pm, 0.0);
+                    }
+                }
+                ParamKind::Array => {
+                    if let Some(setfn) = (*(*pm).gsu.a).setfn {
+                        setfn(pm, new_zarray(name, std::iter::empty::<&str>())?);
+                    }
+                }
+                ParamKind::Assoc => {
+                    if let Some(setfn) = (*(*pm).gsu.h).setfn {
+                        setfn(pm, bindings::newparamtable(17, c_name.as_ptr()));
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Marks a parameter as exported, like `export name`.
+    ///
+    /// The current value is added to the environment of child processes, and Zsh
+    /// keeps the environment in sync on later assignments. Integer and floating-point
+    /// parameters are exported in their string form.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` contains a null byte.
+    /// - `ParamError::NotFound`: the parameter does not exist or is unset.
+    /// - `ParamError::TypeMismatch`: arrays and associative arrays cannot be exported.
+    pub fn export(name: &str) -> Result<(), ParamError> {
+        let c_name = CString::new(name).map_err(|_| ParamError::InvalidName(name.to_string()))?;
+        unsafe {
+            let pm = find_param(&c_name);
+            if pm.is_null() || (*pm).node.flags & bindings::PM_UNSET as i32 != 0 {
+                return Err(ParamError::NotFound(name.to_string()));
+            }
+            let kind = ParamKind::from_flags((*pm).node.flags);
+            if matches!(kind, ParamKind::Array | ParamKind::Assoc) {
+                return Err(ParamError::TypeMismatch(
+                    name.to_string(),
+                    ParamKind::Scalar,
+                ));
+            }
+
+            // `getsparam` converts numeric values to strings for us.
+            let value = bindings::getsparam(c_name.as_ptr() as *mut c_char);
+            if value.is_null() {
+                return Err(ParamError::NotFound(name.to_string()));
+            }
+            (*pm).node.flags |= bindings::PM_EXPORTED as i32;
+            // `addenv` copies and unmetafies the value into `environ`.
+            bindings::addenv(pm, value);
+        }
+        Ok(())
+    }
+
+    /// Removes the export attribute from a parameter, like `typeset +x name`.
+    ///
+    /// The parameter keeps its value in the shell but is removed from the environment
+    /// of child processes. If the parameter does not exist, this function does nothing.
+    pub fn unexport(name: &str) {
+        let Ok(c_name) = CString::new(name) else {
+            return;
+        };
+        unsafe {
+            let pm = find_param(&c_name);
+            if pm.is_null() {
+                return;
+            }
+            (*pm).node.flags &= !(bindings::PM_EXPORTED as i32);
+            if !(*pm).env.is_null() {
+                bindings::delenv(pm);
             }
         }
     }
 
+    /// Sets a string parameter and exports it, like `export name=value`.
+    ///
+    /// See [`ZshParameter::set_str`] and [`ZshParameter::export`] for the possible errors.
+    pub fn set_exported_str(name: &str, value: &str) -> Result<(), ParamError> {
+        Self::set_str(name, value)?;
+        Self::export(name)
+    }
+
+    /// Returns all set parameters, sorted by name.
+    ///
+    /// This is equivalent to `iter_filtered(ParamFilter::default())`.
+    pub fn iter() -> std::vec::IntoIter<ParamInfo> {
+        Self::iter_filtered(ParamFilter::default())
+    }
+
+    /// Returns the parameters selected by `filter`, sorted by name.
+    ///
+    /// The parameter table is walked with Zsh's `scanhashtable`, so only
+    /// parameters visible in the current scope are reported.
+    pub fn iter_filtered(filter: ParamFilter) -> std::vec::IntoIter<ParamInfo> {
+        let mut params = collect_scan(|| unsafe {
+            let table = bindings::paramtab;
+            if !table.is_null() {
+                bindings::scanhashtable(table, 0, 0, 0, Some(collect_param), 0);
+            }
+        });
+        params.retain(|p| filter.matches(p.flags));
+        params.into_iter()
+    }
+
+    /// Returns the names of all set parameters matching the Zsh glob `pattern`,
+    /// like `typeset -m pattern`.
+    ///
+    /// Returns an empty list if `pattern` is not a valid pattern.
+    pub fn names_matching(pattern: &str) -> Vec<String> {
+        let Ok(c_pattern) = CString::new(pattern) else {
+            return Vec::new();
+        };
+        let params = collect_scan(|| unsafe {
+            let table = bindings::paramtab;
+            if table.is_null() {
+                return;
+            }
+            // `patcompile` expects a metafied, tokenized pattern.
+            let pat = bindings::ztrdup_metafy(c_pattern.as_ptr());
+            bindings::tokenize(pat);
+            let prog = bindings::patcompile(pat, bindings::PAT_ZDUP as i32, std::ptr::null_mut());
+            bindings::zsfree(pat);
+            if prog.is_null() {
+                return;
+            }
+            bindings::scanmatchtable(table, prog, 0, 0, 0, Some(collect_param), 0);
+            bindings::freepatprog(prog);
+        });
+        params
+            .into_iter()
+            .filter(|p| ParamFilter::default().matches(p.flags))
+            .map(|p| p.name)
+            .collect()
+    }
+
     /// Unsets (deletes) a parameter in Zsh.
     ///
     /// If the parameter does not exist, this function does nothing.
@@ -150,3 +1190,154 @@ impl ZshParameter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unmetafy(raw: &[u8]) -> Vec<u8> {
+        let c_raw = CString::new(raw).unwrap();
+        unsafe { unmetafy_bytes(c_raw.as_ptr()) }
+    }
+
+    #[test]
+    fn unmetafy_decodes_meta_escapes() {
+        // Zsh stores bytes such as NUL and Meta itself as `Meta, byte ^ 32`.
+        assert_eq!(unmetafy(&[b'a', META, 0x20, b'b']), vec![b'a', 0x00, b'b']);
+        assert_eq!(unmetafy(&[META, META ^ 32]), vec![META]);
+        assert_eq!(unmetafy(&[META, 0x9b ^ 32, b'c']), vec![0x9b, b'c']);
+    }
+
+    #[test]
+    fn unmetafy_keeps_plain_and_latin1_bytes() {
+        assert_eq!(unmetafy(b"plain"), b"plain".to_vec());
+        // "café" in latin-1 is not valid UTF-8 and must be kept as is.
+        assert_eq!(
+            unmetafy(&[b'c', b'a', b'f', 0xe9]),
+            vec![b'c', b'a', b'f', 0xe9]
+        );
+        assert_eq!(unmetafy("日本".as_bytes()), "日本".as_bytes().to_vec());
+    }
+
+    #[test]
+    fn unmetafy_drops_trailing_meta() {
+        assert_eq!(unmetafy(&[b'x', META]), vec![b'x']);
+    }
+
+    #[test]
+    fn param_kind_follows_type_flags() {
+        assert_eq!(
+            ParamKind::from_flags(bindings::PM_SCALAR as i32),
+            ParamKind::Scalar
+        );
+        assert_eq!(
+            ParamKind::from_flags(bindings::PM_INTEGER as i32),
+            ParamKind::Integer
+        );
+        assert_eq!(
+            ParamKind::from_flags(bindings::PM_EFLOAT as i32),
+            ParamKind::Float
+        );
+        assert_eq!(
+            ParamKind::from_flags(bindings::PM_FFLOAT as i32),
+            ParamKind::Float
+        );
+        assert_eq!(
+            ParamKind::from_flags(bindings::PM_ARRAY as i32),
+            ParamKind::Array
+        );
+        assert_eq!(
+            ParamKind::from_flags(bindings::PM_HASHED as i32),
+            ParamKind::Assoc
+        );
+        // Attribute bits do not change the type.
+        let flags = bindings::PM_ARRAY | bindings::PM_UNIQUE | bindings::PM_TIED;
+        assert_eq!(ParamKind::from_flags(flags as i32), ParamKind::Array);
+    }
+
+    #[test]
+    fn param_flags_predicates() {
+        let flags = ParamFlags::from_bits(
+            (bindings::PM_SCALAR | bindings::PM_READONLY | bindings::PM_EXPORTED) as i32,
+        );
+        assert!(flags.is_readonly());
+        assert!(flags.is_exported());
+        assert!(!flags.is_tied());
+        assert!(!flags.is_special());
+        assert!(!flags.is_unset());
+        assert_eq!(flags.kind(), ParamKind::Scalar);
+
+        let flags = ParamFlags::from_bits(
+            (bindings::PM_ARRAY | bindings::PM_UNIQUE | bindings::PM_LOCAL | bindings::PM_UNSET)
+                as i32,
+        );
+        assert!(flags.is_unique());
+        assert!(flags.is_local());
+        assert!(flags.is_unset());
+        assert!(!flags.is_readonly());
+        assert!(!flags.is_lower() && !flags.is_upper());
+        assert_eq!(flags.kind(), ParamKind::Array);
+        assert_eq!(ParamFlags::from_bits(flags.bits()), flags);
+    }
+
+    #[test]
+    fn param_filter_includes_and_excludes() {
+        let plain = ParamFlags::from_bits(bindings::PM_SCALAR as i32);
+        let unset = ParamFlags::from_bits((bindings::PM_SCALAR | bindings::PM_UNSET) as i32);
+        let special = ParamFlags::from_bits((bindings::PM_INTEGER | bindings::PM_SPECIAL) as i32);
+        let unset_special =
+            ParamFlags::from_bits((bindings::PM_UNSET | bindings::PM_SPECIAL) as i32);
+        let all = [plain, unset, special, unset_special];
+        let selected = |skip_unset, skip_special| {
+            let filter = ParamFilter {
+                skip_unset,
+                skip_special,
+            };
+            all.map(|flags| filter.matches(flags))
+        };
+
+        assert_eq!(selected(false, false), [true, true, true, true]);
+        assert_eq!(selected(true, false), [true, false, true, false]);
+        assert_eq!(selected(false, true), [true, true, false, false]);
+        assert_eq!(selected(true, true), [true, false, false, false]);
+        assert_eq!(
+            ParamFilter::default(),
+            ParamFilter {
+                skip_unset: true,
+                skip_special: false,
+            }
+        );
+    }
+
+    #[test]
+    fn number_round_trips_through_mnumber() {
+        for n in [
+            ZshNumber::Int(0),
+            ZshNumber::Int(-42),
+            ZshNumber::Int(bindings::zlong::MAX),
+            ZshNumber::Float(1.5),
+            ZshNumber::Float(-0.25),
+        ] {
+            assert_eq!(ZshNumber::from_raw(n.into_raw()), n);
+        }
+    }
+
+    #[test]
+    fn number_type_selects_int_or_float() {
+        let int = ZshNumber::Int(7).into_raw();
+        assert_eq!(int.type_, bindings::MN_INTEGER as i32);
+        let float = ZshNumber::Float(7.0).into_raw();
+        assert_eq!(float.type_, bindings::MN_FLOAT as i32);
+
+        // Only the `MN_FLOAT` bit decides; other bits such as `MN_UNSET` are ignored.
+        let mut raw: bindings::mnumber = unsafe { std::mem::zeroed() };
+        raw.u.d = 2.5;
+        raw.type_ = (bindings::MN_FLOAT | bindings::MN_UNSET) as i32;
+        assert_eq!(ZshNumber::from_raw(raw), ZshNumber::Float(2.5));
+
+        let three: bindings::zlong = 3;
+        assert_eq!(ZshNumber::from(three), ZshNumber::Int(3));
+        assert_eq!(ZshNumber::from(0.5), ZshNumber::Float(0.5));
+        assert_eq!(ZshNumber::Int(3).as_f64(), 3.0);
+    }
+}
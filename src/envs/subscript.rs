@@ -0,0 +1,184 @@
+//! Subscript access (`name[i]`, `name[i,j]`, `name[(r)pattern]`) on parameters.
+//!
+//! Subscripts are evaluated by Zsh itself through `fetchvalue`, so every subscript
+//! form and flag supported by the shell works here as well.
+use super::{
+    ParamError, ZshParameter, ZshValue, collect_array, heap_metafy, new_zarray, unmetafy_bytes,
+};
+use crate::bindings;
+use std::ffi::CString;
+
+/// Resolves `name[subscript]` into a Zsh `value` using `fetchvalue`.
+///
+/// `flags` are `SCANPM_*` flags; pass `SCANPM_ASSIGNING` to create missing
+/// associative array elements. Returns `None` if the expression does not name
+/// an existing parameter or is not entirely consumed by the parser.
+///
+/// # Safety
+/// The returned value points into `vbuf` and Zsh's heap; it must not outlive either.
+unsafe fn fetch_subscript(
+    vbuf: &mut bindings::value,
+    name: &str,
+    subscript: &str,
+    flags: i32,
+) -> Option<bindings::Value> {
+    // The expression is parsed in place, so give Zsh its own (heap) copy.
+    let expr = format!("{}[{}]", name, subscript);
+    let mut ptr = heap_metafy(expr.as_bytes());
+    unsafe {
+        let v = bindings::fetchvalue(vbuf, &mut ptr, 1, flags);
+        if v.is_null() || *ptr != 0 {
+            None
+        } else {
+            Some(v)
+        }
+    }
+}
+
+/// Runs `f` with Zsh's error flag cleared and reports whether `f` raised an error.
+///
+/// The caller's `errflag` is restored afterwards, so an error raised here does not
+/// abort the surrounding shell code and an earlier error is not mistaken for ours.
+/// With `quiet`, `noerrs` is set as well so Zsh does not print the error message.
+fn catch_zsh_error<R>(quiet: bool, f: impl FnOnce() -> R) -> (R, bool) {
+    unsafe {
+        let saved_errflag = bindings::errflag;
+        let saved_noerrs = bindings::noerrs;
+        bindings::errflag = 0;
+        if quiet {
+            bindings::noerrs = 1;
+        }
+        let result = f();
+        let raised = bindings::errflag & bindings::ERRFLAG_ERROR as i32 != 0;
+        bindings::errflag = saved_errflag;
+        bindings::noerrs = saved_noerrs;
+        (result, raised)
+    }
+}
+
+impl ZshParameter {
+    /// Reads `name[subscript]`, e.g. `get_subscript("path", "1")` or
+    /// `get_subscript("fpath", "(r)*site-functions")`.
+    ///
+    /// Single elements and scalar substrings are returned as `ZshValue::Scalar`;
+    /// slices such as `"2,-1"` are returned as `ZshValue::Array`.
+    ///
+    /// Returns `None` if the parameter does not exist or the subscript is invalid.
+    /// Errors Zsh raises while evaluating the subscript are not printed.
+    pub fn get_subscript(name: &str, subscript: &str) -> Option<ZshValue> {
+        let (value, raised) = catch_zsh_error(true, || Self::read_subscript(name, subscript));
+        if raised { None } else { value }
+    }
+
+    fn read_subscript(name: &str, subscript: &str) -> Option<ZshValue> {
+        unsafe {
+            let mut vbuf: bindings::value = std::mem::zeroed();
+            let v = fetch_subscript(&mut vbuf, name, subscript, 0)?;
+            // As in parameter substitution, a value still scanning an array is a slice.
+            if (*v).scanflags != 0 {
+                let arr = bindings::getarrvalue(v);
+                if arr.is_null() {
+                    return None;
+                }
+                let values = collect_array(arr)
+                    .into_iter()
+                    .map(|b| String::from_utf8_lossy(&b).into_owned())
+                    .collect();
+                Some(ZshValue::Array(values))
+            } else {
+                let s = bindings::getstrvalue(v);
+                if s.is_null() {
+                    return None;
+                }
+                let bytes = unmetafy_bytes(s);
+                Some(ZshValue::Scalar(
+                    String::from_utf8_lossy(&bytes).into_owned(),
+                ))
+            }
+        }
+    }
+
+    /// Assigns `value` to `name[subscript]`, like `name[subscript]=value`.
+    ///
+    /// On arrays this replaces a single element (or a slice with one element);
+    /// on scalars it replaces the addressed substring; on associative arrays
+    /// the element is created if needed.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidValue`: `value` contains a null byte.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::NotFound`: the parameter does not exist or the subscript is invalid.
+    /// - `ParamError::Failed`: Zsh rejected the assignment.
+    pub fn set_subscript(name: &str, subscript: &str, value: &str) -> Result<(), ParamError> {
+        let c_value =
+            CString::new(value).map_err(|_| ParamError::InvalidValue(name.to_string()))?;
+        Self::check_subscript_assignable(name)?;
+        let (result, raised) = catch_zsh_error(false, || unsafe {
+            let mut vbuf: bindings::value = std::mem::zeroed();
+            let v = fetch_subscript(
+                &mut vbuf,
+                name,
+                subscript,
+                bindings::SCANPM_ASSIGNING as i32,
+            )
+            .ok_or_else(|| ParamError::NotFound(name.to_string()))?;
+            // `setstrvalue` takes ownership of the metafied value.
+            bindings::setstrvalue(v, bindings::ztrdup_metafy(c_value.as_ptr()));
+            Ok(())
+        });
+        if raised {
+            Err(ParamError::Failed(name.to_string()))
+        } else {
+            result
+        }
+    }
+
+    /// Assigns `values` to the array slice `name[subscript]`, like `name[2,3]=(a b c)`.
+    ///
+    /// The slice is replaced by all of `values`, so the array may grow or shrink.
+    /// An empty `values` removes the addressed elements.
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidValue`: an element contains a null byte.
+    /// - `ParamError::ReadOnly`: the parameter is read-only.
+    /// - `ParamError::NotFound`: the parameter does not exist or the subscript is invalid.
+    /// - `ParamError::OutOfMemory`: Zsh failed to allocate the elements.
+    /// - `ParamError::Failed`: Zsh rejected the assignment.
+    pub fn set_subscript_array(
+        name: &str,
+        subscript: &str,
+        values: Vec<&str>,
+    ) -> Result<(), ParamError> {
+        Self::check_subscript_assignable(name)?;
+        let ptr_array = unsafe { new_zarray(name, values.into_iter())? };
+        let (result, raised) = catch_zsh_error(false, || unsafe {
+            let mut vbuf: bindings::value = std::mem::zeroed();
+            let Some(v) = fetch_subscript(
+                &mut vbuf,
+                name,
+                subscript,
+                bindings::SCANPM_ASSIGNING as i32,
+            ) else {
+                bindings::freearray(ptr_array);
+                return Err(ParamError::NotFound(name.to_string()));
+            };
+            // `setarrvalue` takes ownership of the array and its elements.
+            bindings::setarrvalue(v, ptr_array);
+            Ok(())
+        });
+        if raised {
+            Err(ParamError::Failed(name.to_string()))
+        } else {
+            result
+        }
+    }
+
+    /// Checks up front what Zsh would otherwise report with `zerr` during assignment.
+    fn check_subscript_assignable(name: &str) -> Result<(), ParamError> {
+        match Self::flags(name) {
+            Some(flags) if flags.is_readonly() => Err(ParamError::ReadOnly(name.to_string())),
+            Some(_) => Ok(()),
+            None => Err(ParamError::NotFound(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Tied scalar/array parameter pairs, like `typeset -T PATH path :`.
+//!
+//! The pair is wired up the same way `typeset -T` does it: the array is an ordinary
+//! array parameter, and the scalar uses Zsh's `tiedarr_gsu` with a `tieddata`
+//! pointing at the array's storage, so both sides always stay in sync.
+use super::{ParamError, ParamFlags, ZshParameter, ZshValue, find_param, unmetafy_bytes};
+use crate::bindings;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+impl ZshParameter {
+    /// Ties the scalar `scalar` to the array `array`, joined by `separator`,
+    /// like `typeset -T scalar array separator`.
+    ///
+    /// The array keeps its current elements if it exists; otherwise it is
+    /// initialised by splitting the current value of `scalar` on `separator`.
+    /// Tying a pair that is already tied to each other does nothing. If `scalar`
+    /// is exported, it stays exported with the joined value.
+    ///
+    /// Both parameters are checked before anything is changed, and if Zsh fails to
+    /// create the pair, the original parameters are restored.
+    ///
+    /// ```ignore
+    /// ZshParameter::tie("MYMOD_PATH", "mymod_path", ':')?;
+    /// ZshParameter::set_array("mymod_path", vec!["/a", "/b"])?;
+    /// assert_eq!(ZshParameter::get_str("MYMOD_PATH").as_deref(), Some("/a:/b"));
+    /// ```
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: a name is invalid, or both names are the same.
+    /// - `ParamError::InvalidValue`: `separator` is not a single-byte character.
+    /// - `ParamError::ReadOnly`: one of the parameters is read-only.
+    /// - `ParamError::Failed`: one of the parameters is already tied to another one
+    ///   or is special, or Zsh failed to create the pair.
+    pub fn tie(scalar: &str, array: &str, separator: char) -> Result<(), ParamError> {
+        if scalar == array {
+            return Err(ParamError::InvalidName(array.to_string()));
+        }
+        if !separator.is_ascii() || separator == '\0' {
+            return Err(ParamError::InvalidValue(scalar.to_string()));
+        }
+        let c_scalar =
+            CString::new(scalar).map_err(|_| ParamError::InvalidName(scalar.to_string()))?;
+        let c_array =
+            CString::new(array).map_err(|_| ParamError::InvalidName(array.to_string()))?;
+
+        let sides = [scalar, array].map(|name| (Self::flags(name), Self::tied_pair(name)));
+        if check_tie(scalar, array, &sides)? {
+            return Ok(());
+        }
+        let exported = sides[0].0.is_some_and(ParamFlags::is_exported);
+
+        // Keep the existing array, or split the scalar, as `typeset -T` does.
+        let saved_scalar = Self::get(scalar);
+        let saved_array = Self::get(array);
+        let initial: Vec<String> = match &saved_array {
+            Some(ZshValue::Array(values)) => values.clone(),
+            _ => Self::get_str(scalar)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.split(separator).map(str::to_string).collect())
+                .unwrap_or_default(),
+        };
+
+        Self::unset(scalar);
+        Self::unset(array);
+        let result = Self::set_array(array, initial.iter().map(String::as_str).collect())
+            .and_then(|()| unsafe { link_tied(&c_scalar, &c_array, separator) })
+            .and_then(|()| {
+                if exported {
+                    Self::export(scalar)
+                } else {
+                    Ok(())
+                }
+            });
+        if let Err(err) = result {
+            // Put both parameters back as they were before the call.
+            Self::unset(scalar);
+            Self::unset(array);
+            if let Some(value) = &saved_array {
+                let _ = restore_value(array, value);
+            }
+            if let Some(value) = &saved_scalar {
+                let _ = restore_value(scalar, value);
+                if exported {
+                    let _ = Self::export(scalar);
+                }
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the parameter `name` is tied to, if any.
+    ///
+    /// For `PATH` this returns `Some("path")`, and for `path` it returns `Some("PATH")`.
+    pub fn tied_pair(name: &str) -> Option<String> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            let pm = find_param(&c_name);
+            if pm.is_null()
+                || (*pm).node.flags & bindings::PM_TIED as i32 == 0
+                || (*pm).ename.is_null()
+            {
+                return None;
+            }
+            Some(String::from_utf8_lossy(&unmetafy_bytes((*pm).ename)).into_owned())
+        }
+    }
+}
+
+/// Checks whether `scalar` and `array` can be tied, given the flags and tied partner
+/// of each (in that order).
+///
+/// Returns `Ok(true)` if they are already tied to each other and nothing needs to
+/// be done. Read-only parameters are rejected before anything else.
+fn check_tie(
+    scalar: &str,
+    array: &str,
+    sides: &[(Option<ParamFlags>, Option<String>); 2],
+) -> Result<bool, ParamError> {
+    let names = [scalar, array];
+    for (name, (flags, _)) in names.iter().zip(sides) {
+        if flags.is_some_and(ParamFlags::is_readonly) {
+            return Err(ParamError::ReadOnly(name.to_string()));
+        }
+    }
+    let paired = |other: &Option<String>| other.as_deref().is_some_and(|o| names.contains(&o));
+    if sides.iter().all(|(_, other)| paired(other)) {
+        return Ok(true);
+    }
+    for (name, (flags, other)) in names.iter().zip(sides) {
+        // The GSU of a special parameter cannot be replaced by the tied one.
+        if other.is_some() || flags.is_some_and(ParamFlags::is_special) {
+            return Err(ParamError::Failed(name.to_string()));
+        }
+    }
+    Ok(false)
+}
+
+/// Assigns a value saved with [`ZshParameter::get`] back to `name`.
+fn restore_value(name: &str, value: &ZshValue) -> Result<(), ParamError> {
+    match value {
+        ZshValue::Scalar(s) => ZshParameter::set_str(name, s),
+        ZshValue::Integer(i) => ZshParameter::set_int(name, *i),
+        ZshValue::Float(f) => ZshParameter::set_float(name, *f),
+        ZshValue::Array(values) => {
+            ZshParameter::set_array(name, values.iter().map(String::as_str).collect())
+        }
+        ZshValue::Assoc(values) => ZshParameter::set_assoc(name, values),
+    }
+}
+
+/// Creates the tied scalar `c_scalar` over the storage of the existing array `c_array`.
+///
+/// # Safety
+/// Must be called on the Zsh main thread, with `c_scalar` unset and `c_array` set.
+unsafe fn link_tied(
+    c_scalar: &CString,
+    c_array: &CString,
+    separator: char,
+) -> Result<(), ParamError> {
+    let name = |c: &CString| c.to_string_lossy().into_owned();
+    unsafe {
+        let apm = find_param(c_array);
+        if apm.is_null() {
+            return Err(ParamError::Failed(name(c_array)));
+        }
+        let pm = bindings::createparam(
+            c_scalar.as_ptr() as *mut c_char,
+            (bindings::PM_SCALAR | bindings::PM_TIED) as i32,
+        );
+        if pm.is_null() {
+            return Err(ParamError::Failed(name(c_scalar)));
+        }
+
+        // The scalar reads and writes the array's storage through `tieddata`.
+        let tdp =
+            bindings::zalloc(std::mem::size_of::<bindings::tieddata>()) as *mut bindings::tieddata;
+        if tdp.is_null() {
+            return Err(ParamError::OutOfMemory);
+        }
+        (*tdp).joinchar = separator as i32;
+        (*tdp).arrptr = &mut (*apm).u.arr;
+        (*pm).u.data = tdp as *mut std::os::raw::c_void;
+        (*pm).gsu.s = &raw const bindings::tiedarr_gsu as bindings::GsuScalar;
+
+        // Each side records the name of the other in `ename`.
+        (*apm).node.flags |= bindings::PM_TIED as i32;
+        (*pm).ename = bindings::ztrdup(c_array.as_ptr());
+        (*apm).ename = bindings::ztrdup(c_scalar.as_ptr());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn side(flags: u32, other: Option<&str>) -> (Option<ParamFlags>, Option<String>) {
+        (
+            Some(ParamFlags::from_bits(flags as i32)),
+            other.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn tie_accepts_plain_and_missing_parameters() {
+        let sides = [side(bindings::PM_SCALAR, None), (None, None)];
+        assert!(matches!(check_tie("S", "s", &sides), Ok(false)));
+    }
+
+    #[test]
+    fn tie_rejects_readonly_before_anything_else() {
+        let sides = [
+            side(bindings::PM_SCALAR, None),
+            side(bindings::PM_ARRAY | bindings::PM_READONLY, None),
+        ];
+        assert!(matches!(check_tie("S", "s", &sides), Err(ParamError::ReadOnly(n)) if n == "s"));
+
+        // Even an existing pair is not reported as tied when one side is read-only.
+        let sides = [
+            side(
+                bindings::PM_SCALAR | bindings::PM_TIED | bindings::PM_READONLY,
+                Some("s"),
+            ),
+            side(bindings::PM_ARRAY | bindings::PM_TIED, Some("S")),
+        ];
+        assert!(matches!(check_tie("S", "s", &sides), Err(ParamError::ReadOnly(n)) if n == "S"));
+    }
+
+    #[test]
+    fn tie_of_an_existing_pair_does_nothing() {
+        let sides = [
+            side(
+                bindings::PM_SCALAR | bindings::PM_TIED | bindings::PM_SPECIAL,
+                Some("path"),
+            ),
+            side(
+                bindings::PM_ARRAY | bindings::PM_TIED | bindings::PM_SPECIAL,
+                Some("PATH"),
+            ),
+        ];
+        assert!(matches!(check_tie("PATH", "path", &sides), Ok(true)));
+    }
+
+    #[test]
+    fn tie_rejects_other_pairs_and_specials() {
+        let sides = [
+            side(bindings::PM_SCALAR | bindings::PM_TIED, Some("other")),
+            side(bindings::PM_ARRAY, None),
+        ];
+        assert!(matches!(check_tie("S", "s", &sides), Err(ParamError::Failed(n)) if n == "S"));
+
+        let sides = [
+            side(bindings::PM_SCALAR, None),
+            side(bindings::PM_INTEGER | bindings::PM_SPECIAL, None),
+        ];
+        assert!(matches!(check_tie("S", "s", &sides), Err(ParamError::Failed(n)) if n == "s"));
+    }
+}
@@ -0,0 +1,418 @@
+//! Parameter watchers: Rust callbacks invoked whenever a parameter is assigned.
+//!
+//! A watcher replaces the parameter's GSU (get/set/unset) table with a copy whose
+//! setter calls the original one and then reports the old and new values.
+use super::{
+    ParamError, ParamKind, ZshParameter, ZshValue, collect_array, find_param, unmetafy_bytes,
+};
+use crate::bindings;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{Arc, Mutex};
+
+/// Callback invoked with the old and new value of a watched parameter.
+///
+/// A value is `None` when the parameter was unset before or after the assignment.
+pub type ParamWatcher = Arc<dyn Fn(Option<&ZshValue>, Option<&ZshValue>) + Send + Sync>;
+
+/// A replacement GSU table followed by a pointer to the table it replaced.
+///
+/// `gsu` is the first field, so the pointer Zsh stores in `param.gsu` also points
+/// at the whole struct and the setter trampolines can always find the original.
+#[repr(C)]
+struct Chained<G> {
+    gsu: G,
+    original: *const c_void,
+}
+
+/// The replacement GSU table installed on a watched parameter.
+///
+/// It is boxed so its address stays stable while Zsh points at it.
+enum WatchGsu {
+    Scalar(Box<Chained<bindings::gsu_scalar>>),
+    Integer(Box<Chained<bindings::gsu_integer>>),
+    Float(Box<Chained<bindings::gsu_float>>),
+    Array(Box<Chained<bindings::gsu_array>>),
+    Hash(Box<Chained<bindings::gsu_hash>>),
+}
+
+impl WatchGsu {
+    /// Copies the original GSU table of `pm`, replacing its setter with a watching trampoline.
+    ///
+    /// # Safety
+    /// `pm` must be a valid parameter node with a GSU table matching `kind`.
+    unsafe fn wrap(pm: bindings::Param, kind: ParamKind) -> Self {
+        unsafe {
+            let original = gsu_ptr(pm);
+            match kind {
+                ParamKind::Scalar => WatchGsu::Scalar(Box::new(Chained {
+                    gsu: bindings::gsu_scalar {
+                        setfn: Some(watch_scalar_setfn),
+                        ..*(*pm).gsu.s
+                    },
+                    original,
+                })),
+                ParamKind::Integer => WatchGsu::Integer(Box::new(Chained {
+                    gsu: bindings::gsu_integer {
+                        setfn: Some(watch_integer_setfn),
+                        ..*(*pm).gsu.i
+                    },
+                    original,
+                })),
+                ParamKind::Float => WatchGsu::Float(Box::new(Chained {
+                    gsu: bindings::gsu_float {
+                        setfn: Some(watch_float_setfn),
+                        ..*(*pm).gsu.f
+                    },
+                    original,
+                })),
+                ParamKind::Array => WatchGsu::Array(Box::new(Chained {
+                    gsu: bindings::gsu_array {
+                        setfn: Some(watch_array_setfn),
+                        ..*(*pm).gsu.a
+                    },
+                    original,
+                })),
+                ParamKind::Assoc => WatchGsu::Hash(Box::new(Chained {
+                    gsu: bindings::gsu_hash {
+                        setfn: Some(watch_hash_setfn),
+                        ..*(*pm).gsu.h
+                    },
+                    original,
+                })),
+            }
+        }
+    }
+
+    /// Returns the address of the replacement table, as stored in `param.gsu`.
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            WatchGsu::Scalar(g) => &g.gsu as *const bindings::gsu_scalar as *const c_void,
+            WatchGsu::Integer(g) => &g.gsu as *const bindings::gsu_integer as *const c_void,
+            WatchGsu::Float(g) => &g.gsu as *const bindings::gsu_float as *const c_void,
+            WatchGsu::Array(g) => &g.gsu as *const bindings::gsu_array as *const c_void,
+            WatchGsu::Hash(g) => &g.gsu as *const bindings::gsu_hash as *const c_void,
+        }
+    }
+}
+
+/// A watched parameter and the state needed to chain to and restore its original GSU.
+struct Watch {
+    name: String,
+    pm: bindings::Param,
+    original: *const c_void,
+    replacement: WatchGsu,
+    callback: ParamWatcher,
+}
+
+/// `Watch` holds raw pointers into Zsh's parameter table. Like the other Zsh
+/// structures in this crate, they are only ever touched from the Zsh main thread.
+unsafe impl Send for Watch {}
+
+/// All active watchers, looked up by parameter node from the setter trampolines.
+static WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+
+/// Reads the GSU pointer of `pm`. All `gsu` union members are plain pointers.
+unsafe fn gsu_ptr(pm: bindings::Param) -> *const c_void {
+    unsafe { (*pm).gsu.s as *const c_void }
+}
+
+/// Points the GSU of `pm` at `gsu`.
+unsafe fn set_gsu_ptr(pm: bindings::Param, gsu: *const c_void) {
+    unsafe { (*pm).gsu.s = gsu as bindings::GsuScalar };
+}
+
+/// Collects the entries visited by `scanhashtable` while reading a watched hash.
+static HASH_ENTRIES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// `ScanFunc` callback that records each element of a hash in `HASH_ENTRIES`.
+unsafe extern "C" fn collect_hash_entry(node: bindings::HashNode, _flags: c_int) {
+    let pm = node as bindings::Param;
+    let entry = unsafe {
+        if pm.is_null() || (*pm).node.nam.is_null() {
+            return;
+        }
+        let value = match (*(*pm).gsu.s).getfn {
+            Some(getfn) => getfn(pm),
+            None => return,
+        };
+        if value.is_null() {
+            return;
+        }
+        (
+            String::from_utf8_lossy(&unmetafy_bytes((*pm).node.nam)).into_owned(),
+            String::from_utf8_lossy(&unmetafy_bytes(value)).into_owned(),
+        )
+    };
+    if let Ok(mut entries) = HASH_ENTRIES.lock() {
+        entries.push(entry);
+    }
+}
+
+/// Reads the value of `pm` through the getter of its original GSU table.
+///
+/// The node being assigned is read directly, rather than looking the name up again,
+/// so a `local` of the same name never supplies the reported values.
+///
+/// # Safety
+/// `pm` must be a valid parameter node and `original` its original GSU table.
+unsafe fn param_value(pm: bindings::Param, original: *const c_void) -> Option<ZshValue> {
+    unsafe {
+        let flags = (*pm).node.flags;
+        if flags & bindings::PM_UNSET as i32 != 0 {
+            return None;
+        }
+        match ParamKind::from_flags(flags) {
+            ParamKind::Scalar => {
+                let ptr = (*(original as *const bindings::gsu_scalar)).getfn?(pm);
+                if ptr.is_null() {
+                    return None;
+                }
+                Some(ZshValue::Scalar(
+                    String::from_utf8_lossy(&unmetafy_bytes(ptr)).into_owned(),
+                ))
+            }
+            ParamKind::Integer => {
+                let value = (*(original as *const bindings::gsu_integer)).getfn?(pm);
+                Some(ZshValue::Integer(value))
+            }
+            ParamKind::Float => {
+                let value = (*(original as *const bindings::gsu_float)).getfn?(pm);
+                Some(ZshValue::Float(value))
+            }
+            ParamKind::Array => {
+                let ptr = (*(original as *const bindings::gsu_array)).getfn?(pm);
+                if ptr.is_null() {
+                    return None;
+                }
+                Some(ZshValue::Array(
+                    collect_array(ptr)
+                        .into_iter()
+                        .map(|v| String::from_utf8_lossy(&v).into_owned())
+                        .collect(),
+                ))
+            }
+            ParamKind::Assoc => {
+                let table = (*(original as *const bindings::gsu_hash)).getfn?(pm);
+                if table.is_null() {
+                    return None;
+                }
+                if let Ok(mut entries) = HASH_ENTRIES.lock() {
+                    entries.clear();
+                }
+                bindings::scanhashtable(
+                    table,
+                    0,
+                    0,
+                    bindings::PM_UNSET as i32,
+                    Some(collect_hash_entry),
+                    0,
+                );
+                let entries = HASH_ENTRIES
+                    .lock()
+                    .map(|mut entries| std::mem::take(&mut *entries))
+                    .unwrap_or_default();
+                Some(ZshValue::Assoc(entries.into_iter().collect()))
+            }
+        }
+    }
+}
+
+/// Returns the original GSU table chained from the replacement installed on `pm`.
+///
+/// # Safety
+/// `pm`'s GSU must be a `Chained<G>` created by [`WatchGsu::wrap`], which holds
+/// whenever one of the setter trampolines is called through it.
+unsafe fn original_gsu<G>(pm: bindings::Param) -> *const c_void {
+    unsafe { (*(gsu_ptr(pm) as *const Chained<G>)).original }
+}
+
+/// Runs an assignment on a watched parameter and reports the change.
+///
+/// `set` receives the original GSU table and must perform the actual assignment.
+/// It always runs, even when no entry in `WATCHES` matches `pm`, so that no
+/// assignment is lost; only the callback depends on a matching watch.
+/// The lock is released before calling into Zsh or the callback, so callbacks
+/// may freely read or assign parameters, including the watched one.
+unsafe fn watched_set(
+    pm: bindings::Param,
+    original: *const c_void,
+    set: impl FnOnce(*const c_void),
+) {
+    let current = unsafe { gsu_ptr(pm) };
+    let callback = WATCHES.lock().ok().and_then(|watches| {
+        watches
+            .iter()
+            .find(|w| w.pm == pm && w.replacement.as_ptr() == current)
+            .map(|w| Arc::clone(&w.callback))
+    });
+    let Some(callback) = callback else {
+        set(original);
+        return;
+    };
+    let old = unsafe { param_value(pm, original) };
+    set(original);
+    let new = unsafe { param_value(pm, original) };
+    callback(old.as_ref(), new.as_ref());
+}
+
+unsafe extern "C" fn watch_scalar_setfn(pm: bindings::Param, value: *mut c_char) {
+    unsafe {
+        watched_set(pm, original_gsu::<bindings::gsu_scalar>(pm), |orig| {
+            if let Some(setfn) = (*(orig as *const bindings::gsu_scalar)).setfn {
+                setfn(pm, value);
+            }
+        })
+    }
+}
+
+unsafe extern "C" fn watch_integer_setfn(pm: bindings::Param, value: bindings::zlong) {
+    unsafe {
+        watched_set(pm, original_gsu::<bindings::gsu_integer>(pm), |orig| {
+            if let Some(setfn) = (*(orig as *const bindings::gsu_integer)).setfn {
+                setfn(pm, value);
+            }
+        })
+    }
+}
+
+unsafe extern "C" fn watch_float_setfn(pm: bindings::Param, value: f64) {
+    unsafe {
+        watched_set(pm, original_gsu::<bindings::gsu_float>(pm), |orig| {
+            if let Some(setfn) = (*(orig as *const bindings::gsu_float)).setfn {
+                setfn(pm, value);
+            }
+        })
+    }
+}
+
+unsafe extern "C" fn watch_array_setfn(pm: bindings::Param, value: *mut *mut c_char) {
+    unsafe {
+        watched_set(pm, original_gsu::<bindings::gsu_array>(pm), |orig| {
+            if let Some(setfn) = (*(orig as *const bindings::gsu_array)).setfn {
+                setfn(pm, value);
+            }
+        })
+    }
+}
+
+unsafe extern "C" fn watch_hash_setfn(pm: bindings::Param, value: bindings::HashTable) {
+    unsafe {
+        watched_set(pm, original_gsu::<bindings::gsu_hash>(pm), |orig| {
+            if let Some(setfn) = (*(orig as *const bindings::gsu_hash)).setfn {
+                setfn(pm, value);
+            }
+        })
+    }
+}
+
+/// Restores the original GSU of a watched parameter, if it is still installed.
+///
+/// The parameter may have been unset and freed since it was watched, so it is
+/// looked up again by name (including hidden outer scopes) rather than trusting `w.pm`.
+fn restore(w: &Watch) {
+    let Ok(c_name) = CString::new(w.name.as_str()) else {
+        return;
+    };
+    unsafe {
+        let mut pm = find_param(&c_name);
+        while !pm.is_null() {
+            if pm == w.pm && gsu_ptr(pm) == w.replacement.as_ptr() {
+                set_gsu_ptr(pm, w.original);
+                return;
+            }
+            pm = (*pm).old;
+        }
+    }
+}
+
+impl ZshParameter {
+    /// Calls `callback` with the old and new value whenever `name` is assigned.
+    ///
+    /// The parameter's setter is wrapped: the original setter runs first, then the
+    /// callback receives the values before and after the assignment. Watching an
+    /// already watched parameter replaces its callback.
+    ///
+    /// The watch is bound to the parameter visible now; a `local` of the same name
+    /// created later is not watched. Assigning a single element of an associative
+    /// array (`h[k]=v`) goes through the element rather than the hash's setter, so
+    /// it does not call `callback`; only whole assignments such as `h=(k v)` do. Watchers are removed with [`ZshParameter::unwatch`],
+    /// and automatically when the module is cleaned up.
+    ///
+    /// ```ignore
+    /// ZshParameter::watch("PROMPT_THEME", |old, new| {
+    ///     eprintln!("theme changed: {:?} -> {:?}", old, new);
+    /// })?;
+    /// ```
+    ///
+    /// # Errors
+    /// - `ParamError::InvalidName`: `name` contains a null byte.
+    /// - `ParamError::NotFound`: the parameter does not exist.
+    pub fn watch(
+        name: &str,
+        callback: impl Fn(Option<&ZshValue>, Option<&ZshValue>) + Send + Sync + 'static,
+    ) -> Result<(), ParamError> {
+        let c_name = CString::new(name).map_err(|_| ParamError::InvalidName(name.to_string()))?;
+        let callback: ParamWatcher = Arc::new(callback);
+        let mut watches = WATCHES
+            .lock()
+            .map_err(|_| ParamError::Failed(name.to_string()))?;
+
+        unsafe {
+            let pm = find_param(&c_name);
+            if pm.is_null() {
+                return Err(ParamError::NotFound(name.to_string()));
+            }
+            // A freed parameter's node may be reused by a new parameter of the same
+            // address; such a watch no longer has its GSU installed and is dropped.
+            let current = gsu_ptr(pm);
+            watches.retain(|w| w.pm != pm || w.replacement.as_ptr() == current);
+            if let Some(w) = watches.iter_mut().find(|w| w.pm == pm) {
+                w.callback = callback;
+                return Ok(());
+            }
+
+            let kind = ParamKind::from_flags((*pm).node.flags);
+            let original = gsu_ptr(pm);
+            let replacement = WatchGsu::wrap(pm, kind);
+            set_gsu_ptr(pm, replacement.as_ptr());
+            watches.push(Watch {
+                name: name.to_string(),
+                pm,
+                original,
+                replacement,
+                callback,
+            });
+        }
+        Ok(())
+    }
+
+    /// Stops watching `name` and restores its original setter.
+    ///
+    /// If the parameter is not watched, this function does nothing.
+    pub fn unwatch(name: &str) {
+        let removed: Vec<Watch> = match WATCHES.lock() {
+            Ok(mut watches) => {
+                let (removed, kept) = std::mem::take(&mut *watches)
+                    .into_iter()
+                    .partition(|w| w.name == name);
+                *watches = kept;
+                removed
+            }
+            Err(_) => return,
+        };
+        removed.iter().for_each(restore);
+    }
+
+    /// Removes all watchers and restores the original setters.
+    ///
+    /// This is called by `export_module!` when the module is cleaned up, so that
+    /// Zsh never calls into a trampoline of an unloaded module.
+    pub fn unwatch_all() {
+        let removed = match WATCHES.lock() {
+            Ok(mut watches) => std::mem::take(&mut *watches),
+            Err(_) => return,
+        };
+        removed.iter().for_each(restore);
+    }
+}
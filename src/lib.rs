@@ -8,17 +8,22 @@
 //! - Zsh のメモリ管理 (`ZBox`, `ZString`) を利用した安全なメモリ操作。
 //! - Zsh の `Module` システムへの統合を簡素化するマクロ (`export_module!`)。
 //! - ビルトインコマンド、条件定義、数式関数、パラメータ定義などの Zsh 機能の登録。
+//! - 属性マクロ (`#[zsh_builtin]`) による型付きの引数を持つビルトインコマンドの定義。
 //! - Zsh のフックシステム (`Hook`) とのインタラクション。
 //! - Zsh コマンド (`shell::eval`) の実行。
+//! - Zsh の標準出力・標準エラー出力への書き込み (`ZshOut`, `ZshErr`, `warn!`, `error!`)。
 mod envs;
 mod macros;
 mod module;
+mod output;
 mod shell;
 mod zalloc;
 pub use crate::module::*;
 pub use envs::*;
+pub use output::*;
 pub use shell::*;
 pub use zalloc::*;
+pub use zsh_system_macros::zsh_builtin;
 /// Zsh C APIへのFFIバインディングが含まれています。`build.rs`によって生成されます。
 #[doc(hidden)]
 pub mod bindings;
@@ -29,6 +34,21 @@ pub mod bindings;
 pub mod __private_api {
     use crate::Features;
     use crate::bindings;
+    use crate::module::BuiltinSpec;
+
+    pub use crate::module::args::{convert_arg, convert_optional_arg, convert_rest_args};
+
+    /// モジュールの `boot` が成功した後に、`Features` の設定に応じた処理を行うためのブリッジ。
+    pub fn boot_bridge(features: &Features) {
+        features.boot();
+    }
+
+    /// `#[zsh_builtin]` で定義されたビルトインを登録するためのブリッジ。
+    ///
+    /// 属性マクロが `.init_array` (Mach-Oでは `__DATA,__mod_init_func`) に配置する初期化関数から、モジュールのロード時に呼び出されます。
+    pub fn submit_builtin(spec: fn() -> BuiltinSpec) {
+        crate::module::submit_spec(spec);
+    }
 
     /// Zshがモジュールから提供される「機能の名前リスト」を取得するためのブリッジ。
     ///
@@ -31,17 +31,34 @@ macro_rules! export_module {
                 let mut guard = mutex.lock().expect("Failed to lock module mutex");
                 f(&mut *guard)
             }
+
+            /// 内部用：コンテナが使用中でなければアクセスする
+            pub fn try_with_container<R>(f: impl FnOnce(&mut ModuleContainer) -> R) -> Option<R> {
+                let mut guard = MODULE_STORAGE.get()?.try_lock().ok()?;
+                Some(f(&mut *guard))
+            }
         }
 
         /// 構造体名を通じて実体にアクセスするための拡張を実装
         impl $module_struct {
             /// Rustの他の場所から、このモジュールの実体（Runtimeなどを含む）にアクセスするための関数。
             ///
+            /// 呼び出しの中から再びアクセスするとデッドロックします。
             pub fn with_instance<R>(f: impl FnOnce(&mut Self) -> R) -> R {
                 __zsh_module_impl::with_container(|container| f(&mut container.instance))
             }
         }
 
+        impl $crate::ModuleInstance for $module_struct {
+            fn with_instance<R>(f: impl FnOnce(&mut Self) -> R) -> R {
+                __zsh_module_impl::with_container(|container| f(&mut container.instance))
+            }
+
+            fn try_with_instance<R>(f: impl FnOnce(&mut Self) -> R) -> Option<R> {
+                __zsh_module_impl::try_with_container(|container| f(&mut container.instance))
+            }
+        }
+
         // --- Zsh エントリポイント ---
 
         #[unsafe(no_mangle)]
@@ -51,7 +68,8 @@ macro_rules! export_module {
 
             match instance.setup() {
                 Ok(_) => {
-                    let features_cache = instance.features();
+                    // `#[zsh_builtin]` で定義されたビルトインも併せて提供する
+                    let features_cache = instance.features().add_attribute_builtins();
                     let container = __zsh_module_impl::ModuleContainer {
                         instance,
                         features_cache,
@@ -97,7 +115,10 @@ macro_rules! export_module {
         #[unsafe(no_mangle)]
         pub unsafe extern "C" fn boot_(m: *mut i8) -> i32 {
             __zsh_module_impl::with_container(|c| match c.instance.boot() {
-                Ok(_) => 0,
+                Ok(_) => {
+                    $crate::__private_api::boot_bridge(&c.features_cache);
+                    0
+                }
                 Err(e) => {
                     eprintln!("zsh-system: boot failed: {}", e);
                     1
@@ -107,11 +128,16 @@ macro_rules! export_module {
 
         #[unsafe(no_mangle)]
         pub unsafe extern "C" fn cleanup_(m: *mut i8) -> i32 {
-            __zsh_module_impl::with_container(|c| match c.instance.cleanup() {
-                Ok(_) => 0,
-                Err(e) => {
-                    eprintln!("zsh-system: cleanup failed: {}", e);
-                    1
+            __zsh_module_impl::with_container(|c| {
+                let result = c.instance.cleanup();
+                // 登録したパラメータ監視を解除し、元のセッターに戻す
+                $crate::ZshParameter::unwatch_all();
+                match result {
+                    Ok(_) => 0,
+                    Err(e) => {
+                        eprintln!("zsh-system: cleanup failed: {}", e);
+                        1
+                    }
                 }
             })
         }
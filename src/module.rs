@@ -2,13 +2,18 @@
 //!
 //! Zshモジュールとして機能するために必要なライフサイクルメソッドと、
 //! 提供する機能を定義するためのインターフェースを提供します。
+pub(crate) mod args;
 mod builtin;
+mod completion;
 mod conddef;
 mod features;
 mod hook;
 mod mathfunc;
 mod paramdef;
+pub(crate) mod usage;
+pub use args::FromBuiltinArg;
 pub use builtin::*;
+pub use completion::ArgCompletion;
 pub use conddef::*;
 pub use features::*;
 pub use hook::*;
@@ -59,3 +64,26 @@ pub trait ZshModule {
     /// パラメータ定義などをZshに提供するかを `Features` 構造体として返します。
     fn features(&self) -> Features;
 }
+
+/// `export_module!` で登録されたモジュールのインスタンスへのアクセスを提供するトレイト。
+///
+/// このトレイトは `export_module!` マクロによって自動的に実装されます。
+/// `Features::add_module_builtin` などが、ハンドラにモジュールの状態を
+/// `&mut Self` として渡すために使用します。
+pub trait ModuleInstance: ZshModule + 'static {
+    /// モジュールのインスタンスへの可変参照を受け取るクロージャを実行します。
+    ///
+    /// インスタンスは呼び出しの間ロックされます。この呼び出しの中 (モジュールの `boot` の中を含む)
+    /// から再びインスタンスにアクセスするとデッドロックするため、入れ子になり得る場合は
+    /// [`ModuleInstance::try_with_instance`] を使用してください。
+    ///
+    /// # Panics
+    /// モジュールがまだ初期化されていない (`setup_` が呼ばれていない) 場合にパニックします。
+    fn with_instance<R>(f: impl FnOnce(&mut Self) -> R) -> R;
+
+    /// インスタンスにアクセスできる場合に限り、クロージャを実行します。
+    ///
+    /// モジュールが初期化されていない場合や、インスタンスが既に使用中 (ロック中) の場合は
+    /// クロージャを実行せずに `None` を返します。
+    fn try_with_instance<R>(f: impl FnOnce(&mut Self) -> R) -> Option<R>;
+}
@@ -0,0 +1,192 @@
+//! このモジュールは、ビルトインコマンドの引数を型付きのRustの値に変換するための機能を提供します。
+//!
+//! 主に `#[zsh_builtin]` 属性マクロが生成するコードから使用されます。
+//! 独自の型を引数として受け取りたい場合は [`FromBuiltinArg`] を実装してください。
+use crate::module::builtin::BuiltinCtx;
+use crate::module::usage::print_usage;
+use crate::output::error_named;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// ビルトインの引数 (アンメタファイ済みのバイト列) から変換できる型。
+pub trait FromBuiltinArg: Sized {
+    /// 引数を変換します。失敗した場合は理由を返します。
+    fn from_arg(arg: &[u8]) -> Result<Self, String>;
+}
+
+fn utf8(arg: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(arg).map_err(|_| "not valid UTF-8".to_string())
+}
+
+impl FromBuiltinArg for String {
+    fn from_arg(arg: &[u8]) -> Result<Self, String> {
+        utf8(arg).map(str::to_string)
+    }
+}
+
+impl FromBuiltinArg for OsString {
+    fn from_arg(arg: &[u8]) -> Result<Self, String> {
+        Ok(OsStr::from_bytes(arg).to_os_string())
+    }
+}
+
+impl FromBuiltinArg for PathBuf {
+    fn from_arg(arg: &[u8]) -> Result<Self, String> {
+        Ok(PathBuf::from(OsStr::from_bytes(arg)))
+    }
+}
+
+/// `FromStr` で変換できる型に `FromBuiltinArg` を実装します。
+macro_rules! impl_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl FromBuiltinArg for $t {
+                fn from_arg(arg: &[u8]) -> Result<Self, String> {
+                    utf8(arg)?.parse().map_err(|e| format!("{}", e))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_str!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, char, bool
+);
+
+/// 変換エラーを `name: message` の形式で表示し、続けて使い方を表示します。
+fn report(ctx: &BuiltinCtx, message: &str) -> i32 {
+    error_named(ctx.name(), message);
+    print_usage(ctx.name());
+    1
+}
+
+fn parse<T: FromBuiltinArg>(param: &str, arg: &[u8]) -> Result<T, String> {
+    T::from_arg(arg).map_err(|reason| {
+        format!(
+            "invalid {}: {}: {}",
+            param,
+            String::from_utf8_lossy(arg),
+            reason
+        )
+    })
+}
+
+/// [`convert_arg`] の変換部分。失敗した場合はエラーメッセージを返します。
+fn parse_arg<T: FromBuiltinArg>(args: &[&[u8]], param: &str, index: usize) -> Result<T, String> {
+    match args.get(index) {
+        Some(arg) => parse(param, arg),
+        None => Err(format!("missing argument: {}", param)),
+    }
+}
+
+/// [`convert_optional_arg`] の変換部分。失敗した場合はエラーメッセージを返します。
+fn parse_optional_arg<T: FromBuiltinArg>(
+    args: &[&[u8]],
+    param: &str,
+    index: usize,
+) -> Result<Option<T>, String> {
+    args.get(index).map(|arg| parse(param, arg)).transpose()
+}
+
+/// [`convert_rest_args`] の変換部分。失敗した場合はエラーメッセージを返します。
+fn parse_rest_args<T: FromBuiltinArg>(
+    args: &[&[u8]],
+    param: &str,
+    index: usize,
+) -> Result<Vec<T>, String> {
+    args.iter()
+        .skip(index)
+        .map(|arg| parse(param, arg))
+        .collect()
+}
+
+/// `index` 番目の必須の引数を変換します。
+///
+/// 失敗した場合はエラーと使い方を表示し、ビルトインの終了ステータスを返します。
+#[doc(hidden)]
+pub fn convert_arg<T: FromBuiltinArg>(
+    ctx: &BuiltinCtx,
+    param: &str,
+    index: usize,
+) -> Result<T, i32> {
+    parse_arg(ctx.args_bytes(), param, index).map_err(|message| report(ctx, &message))
+}
+
+/// `index` 番目の省略可能な引数を変換します。
+#[doc(hidden)]
+pub fn convert_optional_arg<T: FromBuiltinArg>(
+    ctx: &BuiltinCtx,
+    param: &str,
+    index: usize,
+) -> Result<Option<T>, i32> {
+    parse_optional_arg(ctx.args_bytes(), param, index).map_err(|message| report(ctx, &message))
+}
+
+/// `index` 番目以降の残りの引数をすべて変換します。
+#[doc(hidden)]
+pub fn convert_rest_args<T: FromBuiltinArg>(
+    ctx: &BuiltinCtx,
+    param: &str,
+    index: usize,
+) -> Result<Vec<T>, i32> {
+    parse_rest_args(ctx.args_bytes(), param, index).map_err(|message| report(ctx, &message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_parsed_and_errors_name_the_parameter() {
+        let args: [&[u8]; 2] = [b"42", b"-1"];
+        assert_eq!(parse_arg::<u32>(&args, "count", 0), Ok(42));
+        assert_eq!(parse_arg::<i64>(&args, "offset", 1), Ok(-1));
+
+        let err = parse_arg::<u32>(&args, "count", 1).unwrap_err();
+        assert!(err.starts_with("invalid count: -1: "), "{}", err);
+        let err = parse_arg::<u8>(&[b"300"], "level", 0).unwrap_err();
+        assert!(err.starts_with("invalid level: 300: "), "{}", err);
+        let err = parse_arg::<f64>(&[b"1.5x"], "ratio", 0).unwrap_err();
+        assert!(err.starts_with("invalid ratio: 1.5x: "), "{}", err);
+    }
+
+    #[test]
+    fn missing_required_argument_is_an_error() {
+        let args: [&[u8]; 1] = [b"a"];
+        assert_eq!(
+            parse_arg::<String>(&args, "dest", 1),
+            Err("missing argument: dest".to_string())
+        );
+    }
+
+    #[test]
+    fn optional_argument_is_none_when_absent() {
+        let args: [&[u8]; 1] = [b"7"];
+        assert_eq!(parse_optional_arg::<u32>(&args, "n", 0), Ok(Some(7)));
+        assert_eq!(parse_optional_arg::<u32>(&args, "n", 1), Ok(None));
+        // 指定された引数の変換に失敗した場合は、省略とは扱わない
+        assert!(parse_optional_arg::<u32>(&[b"x"], "n", 0).is_err());
+    }
+
+    #[test]
+    fn rest_arguments_start_at_index() {
+        let args: [&[u8]; 3] = [b"first", b"1", b"2"];
+        assert_eq!(parse_rest_args::<u32>(&args, "n", 1), Ok(vec![1, 2]));
+        assert_eq!(parse_rest_args::<u32>(&args, "n", 3), Ok(vec![]));
+        assert!(parse_rest_args::<u32>(&args, "n", 0).is_err());
+    }
+
+    #[test]
+    fn paths_and_os_strings_keep_invalid_utf8() {
+        let raw: &[u8] = b"caf\xe9/\xff";
+        let args = [raw];
+        let path: PathBuf = parse_arg(&args, "dest", 0).unwrap();
+        assert_eq!(path.as_os_str().as_bytes(), raw);
+        let os: OsString = parse_arg(&args, "name", 0).unwrap();
+        assert_eq!(os.as_bytes(), raw);
+
+        let err = parse_arg::<String>(&args, "label", 0).unwrap_err();
+        assert!(err.ends_with("not valid UTF-8"), "{}", err);
+    }
+}
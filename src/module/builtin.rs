@@ -3,24 +3,219 @@
 //! Zshのビルトインコマンドは、`BuiltinHandler`トレイトによって定義されたRust関数として実装され、
 //! `Builtin`構造体を通じてZshに登録されます。
 //! 登録されたハンドラは、`dispatch`関数を通じて実行時に呼び出されます。
+//!
+//! ハンドラには関数ポインタのほか、状態を持つクロージャ ([`BuiltinClosure`]) や、
+//! `export_module!` で登録したモジュールのインスタンスを `&mut Self` で受け取る関数も使用できます。
+//!
+//! 引数はアンメタファイされた上でハンドラに渡されます。UTF-8として不正なバイト列
+//! (latin-1 のファイル名など) も失われず、[`BuiltinCtx::args_bytes`] や
+//! [`BuiltinCtx::args_os`] でそのまま取得できます。
+//!
+//! オプション文字列 (例: `"ab:c"`) を宣言したビルトインでは、Zsh自身が `-x` 形式の
+//! オプションを解析し、その結果が [`BuiltinOptions`] としてハンドラに渡されます。
+//! `+x` 形式も解析させる場合は [`BuiltinFlags::PLUS_OPTS`] を指定します。
 use crate::bindings;
+use crate::envs::unmetafy_bytes;
+use crate::module::completion::{ArgCompletion, CompletionSpec};
+use crate::module::usage::{BuiltinHelp, handle_help, help_for, parse_optstr};
 use crate::zalloc::ZString;
-use std::ffi::CStr;
+use std::ffi::OsStr;
 use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
 
 /// Rust側で定義する安全なビルトインコマンドハンドラの型エイリアス。
 ///
 /// コマンド名と引数のスライスを受け取り、Zshの終了ステータスとして`i32`を返します。
+/// 引数のUTF-8として不正なバイトは U+FFFD に置き換えられます。元のバイト列が必要な場合は、
+/// [`BuiltinCtx`] を受け取るクロージャ ([`BuiltinSpec::closure`] など) を使用してください。
 pub type BuiltinHandler = fn(name: &str, args: &[&str]) -> i32;
 
+/// オプションを解析するビルトインコマンドハンドラの型エイリアス。
+///
+/// コマンド名、オプション解析後に残った引数、そして解析済みのオプションを受け取ります。
+/// [`BuiltinHandler`] と同様に、引数は不正なバイトを置き換えた `&str` として渡されます。
+pub type BuiltinOptsHandler = fn(name: &str, args: &[&str], opts: &BuiltinOptions) -> i32;
+
+/// 状態を持つことができるビルトインコマンドハンドラの型エイリアス。
+///
+/// 実行時のコンテキスト ([`BuiltinCtx`]) を受け取り、Zshの終了ステータスとして`i32`を返します。
+pub type BuiltinClosure = Box<dyn FnMut(&mut BuiltinCtx) -> i32 + Send>;
+
+/// 登録されたハンドラの種類。
+#[derive(Clone)]
+pub(crate) enum HandlerKind {
+    /// オプションを受け取らないハンドラ。
+    Plain(BuiltinHandler),
+    /// 解析済みのオプションを受け取るハンドラ。
+    WithOptions(BuiltinOptsHandler),
+    /// 状態を持つクロージャ。呼び出しごとに排他的にロックされます。
+    Closure(Arc<Mutex<BuiltinClosure>>),
+}
+
+impl HandlerKind {
+    /// クロージャからハンドラを作成します。
+    pub(crate) fn closure(f: impl FnMut(&mut BuiltinCtx) -> i32 + Send + 'static) -> Self {
+        HandlerKind::Closure(Arc::new(Mutex::new(Box::new(f))))
+    }
+}
+
+/// ビルトインの引数の変換中に発生する可能性のあるエラーを定義する列挙型。
+#[derive(Debug, Error)]
+pub enum BuiltinArgError {
+    /// 引数がUTF-8として不正なバイト列を含む場合に発生します。
+    #[error("argument {index} is not valid UTF-8: {lossy}")]
+    InvalidUtf8 {
+        /// 不正な引数の位置 (0始まり)。
+        index: usize,
+        /// 不正なバイトを U+FFFD に置き換えた引数。
+        lossy: String,
+    },
+}
+
+/// ビルトインコマンドの実行時コンテキスト。
+///
+/// コマンド名、オプション解析後の引数、解析済みのオプションへのアクセスを提供します。
+pub struct BuiltinCtx<'a> {
+    name: &'a str,
+    args: &'a [&'a str],
+    bytes: &'a [&'a [u8]],
+    os: &'a [&'a OsStr],
+    opts: &'a BuiltinOptions<'a>,
+}
+
+impl<'a> BuiltinCtx<'a> {
+    /// 実行されたビルトインコマンドの名前を返します。
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// オプション解析後に残った引数を返します。
+    ///
+    /// UTF-8として不正なバイトは U+FFFD に置き換えられます。引数の数や位置は変わりません。
+    /// 元のバイト列が必要な場合は [`BuiltinCtx::args_bytes`] を、不正な引数をエラーとして
+    /// 扱いたい場合は [`BuiltinCtx::args_strict`] を使用してください。
+    pub fn args(&self) -> &'a [&'a str] {
+        self.args
+    }
+
+    /// オプション解析後に残った引数を、アンメタファイされたバイト列として返します。
+    pub fn args_bytes(&self) -> &'a [&'a [u8]] {
+        self.bytes
+    }
+
+    /// オプション解析後に残った引数を `OsStr` として返します。
+    ///
+    /// ファイル名を扱う場合は、`Path::new(arg)` でそのままパスに変換できます。
+    pub fn args_os(&self) -> &'a [&'a OsStr] {
+        self.os
+    }
+
+    /// オプション解析後に残った引数を、厳密なUTF-8として返します。
+    ///
+    /// # Errors
+    /// - `BuiltinArgError::InvalidUtf8`: UTF-8として不正な引数が含まれている場合。
+    pub fn args_strict(&self) -> Result<Vec<&'a str>, BuiltinArgError> {
+        self.bytes
+            .iter()
+            .enumerate()
+            .map(|(index, b)| {
+                std::str::from_utf8(b).map_err(|_| BuiltinArgError::InvalidUtf8 {
+                    index,
+                    lossy: String::from_utf8_lossy(b).into_owned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Zshが解析したオプションを返します。
+    pub fn options(&self) -> &'a BuiltinOptions<'a> {
+        self.opts
+    }
+}
+
+/// Zshが解析したビルトインのオプション (`Options`) の読み取り専用ビュー。
+///
+/// Zshの `OPT_ISSET` / `OPT_MINUS` / `OPT_PLUS` / `OPT_ARG` マクロに対応するメソッドを提供します。
+/// ビルトインがオプション文字列を宣言していない場合、全てのオプションは未設定として扱われます。
+pub struct BuiltinOptions<'a> {
+    raw: Option<&'a bindings::options>,
+}
+
+impl<'a> BuiltinOptions<'a> {
+    /// Zshから渡された `Options` ポインタをラップします。
+    ///
+    /// # Safety
+    /// `ops` はNULLであるか、ビルトインの実行中有効な `options` 構造体を指している必要があります。
+    pub unsafe fn from_raw(ops: bindings::Options) -> Self {
+        Self {
+            raw: unsafe { ops.as_ref() },
+        }
+    }
+
+    /// オプション文字 `c` に対応する `ind` の値を返します。
+    fn ind(&self, c: char) -> u8 {
+        match self.raw {
+            Some(ops) if (c as usize) < ops.ind.len() => ops.ind[c as usize] as u8,
+            _ => 0,
+        }
+    }
+
+    /// オプション `c` が `-c` または `+c` の形で指定されたかどうかを返します (`OPT_ISSET`)。
+    pub fn is_set(&self, c: char) -> bool {
+        self.ind(c) != 0
+    }
+
+    /// オプション `c` が `-c` の形で指定されたかどうかを返します (`OPT_MINUS`)。
+    pub fn is_minus(&self, c: char) -> bool {
+        self.ind(c) & 1 != 0
+    }
+
+    /// オプション `c` が `+c` の形で指定されたかどうかを返します (`OPT_PLUS`)。
+    ///
+    /// `+c` 形式はビルトインに [`BuiltinFlags::PLUS_OPTS`] を指定した場合にのみ解析されます。
+    /// 指定していない場合、`+c` は通常の引数として扱われ、このメソッドは常に `false` を返します。
+    pub fn is_plus(&self, c: char) -> bool {
+        self.ind(c) & 2 != 0
+    }
+
+    /// 引数を取るオプション `c` (オプション文字列で `c:` と宣言) の引数を返します (`OPT_ARG`)。
+    ///
+    /// オプションが指定されていないか、引数を持たない場合は `None` を返します。
+    pub fn arg(&self, c: char) -> Option<String> {
+        let ind = self.ind(c);
+        // `OPT_HASARG`: 下位2ビットは -/+ のフラグで、それより上が引数のインデックス+1
+        if ind <= 3 {
+            return None;
+        }
+        let ops = self.raw?;
+        let index = (ind >> 2) as usize - 1;
+        if ops.args.is_null() || index >= ops.argscount as usize {
+            return None;
+        }
+        unsafe {
+            let ptr = *ops.args.add(index);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(String::from_utf8_lossy(&unmetafy_bytes(ptr)).into_owned())
+            }
+        }
+    }
+}
+
 /// Zshのビルトインコマンドの定義をカプセル化する構造体。
 ///
-/// コマンド名、ハンドラ関数、最小・最大引数を保持します。
+/// コマンド名、ハンドラ関数、オプション文字列、最小・最大引数を保持します。
 pub struct Builtin {
     name: ZString,
-    handler: BuiltinHandler,
+    handler: HandlerKind,
+    optstr: Option<ZString>,
     min_args: i32,
     max_args: i32,
+    flags: BuiltinFlags,
+    completion: CompletionSpec,
 }
 
 impl Builtin {
@@ -28,11 +223,30 @@ impl Builtin {
     ///
     /// `name` はビルトインコマンドの名前、`handler` は実行時に呼び出されるRust関数です。
     pub fn new(name: &str, handler: BuiltinHandler) -> Self {
+        Self::with_kind(name, HandlerKind::Plain(handler), None)
+    }
+
+    /// オプション文字列を宣言した `Builtin` インスタンスを作成します。
+    ///
+    /// `optstr` はZshのビルトインと同じ形式で、`"ab:c"` は `-a`, `-c` と、
+    /// 引数を取る `-b arg` を受け付けることを意味します。
+    pub fn with_options(name: &str, optstr: &str, handler: BuiltinOptsHandler) -> Self {
+        Self::with_kind(name, HandlerKind::WithOptions(handler), Some(optstr))
+    }
+
+    pub(crate) fn with_kind(name: &str, handler: HandlerKind, optstr: Option<&str>) -> Self {
         Self {
             name: ZString::new(name),
             handler,
+            optstr: optstr.map(ZString::new),
             min_args: 0,
             max_args: -1, // -1 means no maximum argument limit
+            flags: BuiltinFlags::empty(),
+            completion: CompletionSpec {
+                name: name.to_string(),
+                optstr: optstr.map(str::to_string),
+                ..Default::default()
+            },
         }
     }
 
@@ -48,30 +262,31 @@ impl Builtin {
     unsafe extern "C" fn bridge_handler(
         name: *mut c_char,
         argv: *mut *mut c_char,
-        _ops: bindings::Options,
+        ops: bindings::Options,
         _func: c_int,
     ) -> c_int {
         // 1. コマンド名の取得
         let name_str = if name.is_null() {
-            ""
+            String::new()
         } else {
-            unsafe { CStr::from_ptr(name) }.to_str().unwrap_or("")
+            String::from_utf8_lossy(&unsafe { unmetafy_bytes(name) }).into_owned()
         };
 
-        // 2. 引数配列の構築
+        // 2. 引数配列の構築 (Zshの内部表現からアンメタファイし、不正なUTF-8も保持する)
         let mut args = Vec::new();
         let mut curr = argv;
         unsafe {
             while !curr.is_null() && !(*curr).is_null() {
-                if let Ok(s) = CStr::from_ptr(*curr).to_str() {
-                    args.push(s);
-                }
+                args.push(unmetafy_bytes(*curr));
                 curr = curr.add(1);
             }
         }
 
-        // 3. 登録されたハンドラを呼び出す
-        dispatch(name_str, &args)
+        // 3. 解析済みオプションのビューを作成
+        let opts = unsafe { BuiltinOptions::from_raw(ops) };
+
+        // 4. 登録されたハンドラを呼び出す
+        dispatch_bytes(&name_str, &args, &opts)
     }
 
     /// `Builtin`インスタンスをZshの`builtin`構造体として表現します。
@@ -88,6 +303,10 @@ impl Builtin {
         b.handlerfunc = Some(Self::bridge_handler);
         b.minargs = self.min_args;
         b.maxargs = self.max_args;
+        b.node.flags = self.flags.bits();
+        if let Some(optstr) = &self.optstr {
+            b.optstr = optstr.as_ptr() as *mut c_char;
+        }
 
         // 警告対策: handler フィールドを読み取り、何らかの形で利用する
         // 実際には、この self.handler を zsh 側のデータ領域にポインタとして
@@ -99,26 +318,378 @@ impl Builtin {
 
     /// `Builtin`に格納されているRustハンドラ関数を取得します。
     ///
+    /// オプションを受け取るハンドラやクロージャの場合は、コマンド名で登録されたハンドラを
+    /// 実行する [`dispatch`] を返します。
     /// これは主に内部的な使用とコンパイラの警告回避のためです。
     pub fn handler(&self) -> BuiltinHandler {
-        self.handler
+        match self.handler {
+            HandlerKind::Plain(h) => h,
+            _ => dispatch,
+        }
+    }
+
+    /// ビルトインのコマンド名を返します。
+    pub fn name(&self) -> &str {
+        unsafe { std::ffi::CStr::from_ptr(self.name.as_ptr()) }
+            .to_str()
+            .unwrap_or("")
+    }
+
+    /// 補完関数の生成に必要な情報を返します。
+    pub(crate) fn completion(&self) -> &CompletionSpec {
+        &self.completion
+    }
+
+    /// `Builtin`に格納されているハンドラの種類を取得します。
+    pub(crate) fn handler_kind(&self) -> HandlerKind {
+        self.handler.clone()
+    }
+}
+
+/// ビルトインコマンドの `BINF_*` フラグ。
+///
+/// `|` で組み合わせて [`BuiltinSpec::flags`] に渡します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BuiltinFlags(i32);
+
+impl BuiltinFlags {
+    /// `noglob` や `nocorrect` のような前置コマンド (`BINF_PREFIX`)。
+    pub const PREFIX: Self = Self(bindings::BINF_PREFIX as i32);
+    /// `name=~/dir` のような引数でも `=` の後をチルダ展開する (`BINF_MAGICEQUALS`)。
+    pub const MAGIC_EQUALS: Self = Self(bindings::BINF_MAGICEQUALS as i32);
+    /// `-1` / `+1` のような数値の引数もオプションとして解析する (`BINF_KEEPNUM`)。
+    ///
+    /// 指定しない場合、オプションの解析は数値の引数で終わり、それ以降は引数として渡されます。
+    pub const KEEP_NUM: Self = Self(bindings::BINF_KEEPNUM as i32);
+    /// 単独の `-` を引数として扱い、ハンドラに渡す (`BINF_SKIPDASH`)。
+    ///
+    /// 指定しない場合、単独の `-` はオプションの終わりとして取り除かれます。
+    pub const SKIP_DASH: Self = Self(bindings::BINF_SKIPDASH as i32);
+    /// 不正なオプションをエラーにせず、そこでオプションの解析を終える (`BINF_SKIPINVALID`)。
+    pub const SKIP_INVALID: Self = Self(bindings::BINF_SKIPINVALID as i32);
+    /// `--` をオプションの終わりとして読み飛ばす (`BINF_DASHDASHVALID`)。
+    pub const DASHDASH_VALID: Self = Self(bindings::BINF_DASHDASHVALID as i32);
+    /// オプションを解析せず、すべての引数をそのままハンドラに渡す (`BINF_HANDLES_OPTS`)。
+    pub const HANDLES_OPTS: Self = Self(bindings::BINF_HANDLES_OPTS as i32);
+    /// `+x` 形式もオプションとして解析する (`BINF_PLUSOPTS`)。
+    ///
+    /// 指定しない場合、`+x` は通常の引数としてハンドラに渡されます。
+    pub const PLUS_OPTS: Self = Self(bindings::BINF_PLUSOPTS as i32);
+
+    /// フラグを持たない値を返します。
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// 生の `BINF_*` フラグから作成します。
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+
+    /// 生の `BINF_*` フラグを返します。
+    pub const fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// `other` のフラグがすべて含まれているかを返します。
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for BuiltinFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for BuiltinFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// 引数の数やフラグを宣言的に指定してビルトインコマンドを定義するビルダー。
+///
+/// 引数の数が範囲外の場合は、ハンドラが呼ばれる前にエラーが表示されます。
+/// 検査を行う場所はヘルプの有無によって異なります。
+///
+/// - ヘルプを設定していない場合は、Zsh自身が検査し、
+///   `name: not enough arguments` のような標準のエラーを表示します。
+/// - [`BuiltinSpec::description`] などでヘルプを設定した場合は、`--help` を受け付けるため
+///   Zsh側の検査は無効になり、Rust側で検査してエラーに続けて使い方を表示します。
+///   `name --help` (オプション文字列を宣言している場合は `name -h`) では
+///   生成されたヘルプが表示されます。
+/// オプション文字列で `h` を宣言したビルトインにはヘルプを設定できません。
+/// その場合、[`Features::add_builtin_spec`](crate::Features::add_builtin_spec) は警告を表示し、
+/// ヘルプを持たないビルトインとして追加します。
+///
+/// ```ignore
+/// Features::new().add_builtin_spec(
+///     BuiltinSpec::with_options("greet", "lv:", greet)
+///         .min_args(1)
+///         .max_args(3)
+///         .flags(BuiltinFlags::SKIP_DASH | BuiltinFlags::DASHDASH_VALID)
+///         .description("Print a greeting.")
+///         .usage("name [greeting ...]")
+///         .option_help('l', "use the long form")
+///         .option_help('v', "set the verbosity level"),
+/// )
+/// ```
+pub struct BuiltinSpec {
+    name: String,
+    handler: HandlerKind,
+    optstr: Option<String>,
+    min_args: i32,
+    max_args: i32,
+    flags: BuiltinFlags,
+    description: Option<String>,
+    synopsis: Option<String>,
+    option_help: Vec<(char, String)>,
+    args: Vec<(String, ArgCompletion)>,
+    rest: Option<(String, ArgCompletion)>,
+}
+
+impl BuiltinSpec {
+    /// オプションを解析しないハンドラでビルトインを定義します。
+    pub fn new(name: &str, handler: BuiltinHandler) -> Self {
+        Self::with_kind(name, HandlerKind::Plain(handler), None)
+    }
+
+    /// オプション文字列 (例: `"ab:c"`) を宣言したハンドラでビルトインを定義します。
+    pub fn with_options(name: &str, optstr: &str, handler: BuiltinOptsHandler) -> Self {
+        Self::with_kind(name, HandlerKind::WithOptions(handler), Some(optstr))
+    }
+
+    /// 状態を持つクロージャをハンドラとしてビルトインを定義します。
+    pub fn closure(
+        name: &str,
+        handler: impl FnMut(&mut BuiltinCtx) -> i32 + Send + 'static,
+    ) -> Self {
+        Self::with_kind(name, HandlerKind::closure(handler), None)
+    }
+
+    pub(crate) fn with_kind(name: &str, handler: HandlerKind, optstr: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            handler,
+            optstr: optstr.map(str::to_string),
+            min_args: 0,
+            max_args: -1,
+            flags: BuiltinFlags::empty(),
+            description: None,
+            synopsis: None,
+            option_help: Vec::new(),
+            args: Vec::new(),
+            rest: None,
+        }
+    }
+
+    /// オプション文字列を設定します。
+    ///
+    /// 解析結果は [`BuiltinCtx::options`] で参照できます。
+    pub fn optstr(mut self, optstr: &str) -> Self {
+        self.optstr = Some(optstr.to_string());
+        self
+    }
+
+    /// オプションを除いた引数の最小数を設定します。
+    pub fn min_args(mut self, n: u32) -> Self {
+        self.min_args = n.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// オプションを除いた引数の最大数を設定します。既定では無制限です。
+    pub fn max_args(mut self, n: u32) -> Self {
+        self.max_args = n.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// `BINF_*` フラグを追加します。
+    pub fn flags(mut self, flags: BuiltinFlags) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    /// ヘルプに表示するビルトインの説明を設定します。
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// 使い方に表示する、オプションに続く引数部分の書式 (例: `"count dest [label]"`) を設定します。
+    ///
+    /// コマンド名とオプションの部分はオプション文字列から生成されます。
+    pub fn usage(mut self, synopsis: &str) -> Self {
+        self.synopsis = Some(synopsis.to_string());
+        self
+    }
+
+    /// ヘルプに表示するオプション `c` の説明を設定します。
+    pub fn option_help(mut self, c: char, help: &str) -> Self {
+        self.option_help.push((c, help.to_string()));
+        self
+    }
+
+    /// 位置引数を宣言します。宣言した順に1番目、2番目の引数になります。
+    ///
+    /// 宣言した引数は補完関数の生成に使用されます。[`BuiltinSpec::usage`] を設定していない場合は、
+    /// 使い方の表示にも使用されます (`min_args` 番目以降は省略可能な引数として表示されます)。
+    pub fn arg(mut self, name: &str, completion: ArgCompletion) -> Self {
+        self.args.push((name.to_string(), completion));
+        self
+    }
+
+    /// 宣言した位置引数に続く、残りの引数を宣言します。
+    pub fn rest(mut self, name: &str, completion: ArgCompletion) -> Self {
+        self.rest = Some((name.to_string(), completion));
+        self
+    }
+
+    /// ビルトインのコマンド名を返します。
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 宣言した位置引数から、使い方の引数部分の書式を生成します。
+    fn args_synopsis(&self) -> Option<String> {
+        if self.args.is_empty() && self.rest.is_none() {
+            return None;
+        }
+        let mut parts: Vec<String> = self
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                if (i as i32) < self.min_args {
+                    name.clone()
+                } else {
+                    format!("[{}]", name)
+                }
+            })
+            .collect();
+        if let Some((name, _)) = &self.rest {
+            parts.push(format!("[{} ...]", name));
+        }
+        Some(parts.join(" "))
+    }
+
+    /// オプション文字列で `h` を宣言しているかどうかを返します。
+    fn declares_h(&self) -> bool {
+        parse_optstr(self.optstr.as_deref().unwrap_or("")).any(|(c, _)| c == 'h')
+    }
+
+    /// オプション文字列で `h` を宣言しているにもかかわらず、ヘルプが設定されているかどうかを返します。
+    ///
+    /// この場合は `-h` も `--help` もヘルプの表示に使用できないため、ヘルプは無効になります。
+    pub(crate) fn help_conflicts(&self) -> bool {
+        self.declares_h()
+            && (self.description.is_some()
+                || self.synopsis.is_some()
+                || !self.option_help.is_empty())
+    }
+
+    /// ヘルプが設定されている場合、その表示に必要な情報を返します。
+    ///
+    /// オプション文字列で `h` を宣言している場合は、ヘルプを表示できないため `None` を返します。
+    pub(crate) fn help(&self) -> Option<BuiltinHelp> {
+        if self.declares_h() {
+            return None;
+        }
+        let synopsis = self.synopsis.clone().or_else(|| self.args_synopsis());
+        if self.description.is_none() && synopsis.is_none() && self.option_help.is_empty() {
+            return None;
+        }
+        Some(BuiltinHelp {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            synopsis,
+            optstr: self.optstr.clone(),
+            option_help: self.option_help.clone(),
+            min_args: self.min_args,
+            max_args: self.max_args,
+        })
+    }
+}
+
+impl From<BuiltinSpec> for Builtin {
+    fn from(spec: BuiltinSpec) -> Self {
+        let mut completion = CompletionSpec {
+            name: spec.name.clone(),
+            optstr: spec.optstr.clone(),
+            option_help: spec.option_help.clone(),
+            args: spec.args.clone(),
+            rest: spec.rest.clone(),
+            long_help: false,
+        };
+
+        let Some(help) = spec.help() else {
+            let mut builtin = Builtin::with_kind(&spec.name, spec.handler, spec.optstr.as_deref());
+            builtin.min_args = spec.min_args;
+            builtin.max_args = spec.max_args;
+            builtin.flags = spec.flags;
+            builtin.completion = completion;
+            return builtin;
+        };
+
+        // ヘルプを設定した場合は、使い方を表示できるよう引数の数をRust側で検査する。
+        // また、オプション文字列を宣言していれば `-h` をヘルプ用のオプションとして追加する。
+        let optstr = match &spec.optstr {
+            Some(optstr) if help.uses_h_option() => Some(format!("{}h", optstr)),
+            other => other.clone(),
+        };
+        if help.uses_h_option() {
+            completion.optstr = optstr.clone();
+            completion.option_help.push(('h', "show help".to_string()));
+        } else {
+            completion.long_help = spec.optstr.is_none();
+        }
+        let mut builtin = Builtin::with_kind(&spec.name, spec.handler, optstr.as_deref());
+        builtin.flags = spec.flags;
+        builtin.completion = completion;
+        builtin
     }
 }
 
 // 静的変数による管理
-use std::sync::Mutex;
 /// 登録されたビルトインハンドラをグローバルに管理するためのミューテックス保護されたベクタ。
-/// (`'static str`, `BuiltinHandler`) のタプルを格納します。
-static HANDLERS: Mutex<Vec<(&'static str, BuiltinHandler)>> = Mutex::new(Vec::new());
+/// (コマンド名, `HandlerKind`) のタプルを格納します。
+static HANDLERS: Mutex<Vec<(String, HandlerKind)>> = Mutex::new(Vec::new());
+
+/// `#[zsh_builtin]` で定義され、モジュールのロード時に登録されたビルトインの定義関数。
+static SUBMITTED: Mutex<Vec<fn() -> BuiltinSpec>> = Mutex::new(Vec::new());
+
+/// `#[zsh_builtin]` で定義されたビルトインを登録します。
+///
+/// 属性マクロが生成する初期化関数から、モジュールのロード時に呼び出されます。
+pub(crate) fn submit_spec(spec: fn() -> BuiltinSpec) {
+    if let Ok(mut s) = SUBMITTED.lock() {
+        s.push(spec);
+    }
+}
+
+/// `#[zsh_builtin]` で登録されたビルトインの定義をすべて作成します。
+pub(crate) fn submitted_specs() -> Vec<BuiltinSpec> {
+    // 定義関数の中でロックを取らないよう、複製してから呼び出す
+    let specs = SUBMITTED.lock().map(|s| s.clone()).unwrap_or_default();
+    specs.into_iter().map(|spec| spec()).collect()
+}
 
 /// ビルトインコマンドのハンドラをグローバルディスパッチャに登録します。
 ///
 /// 同じ名前のハンドラが既に登録されている場合は、重複して登録されません。
-pub fn register_handler(name: &'static str, handler: BuiltinHandler) {
+pub fn register_handler(name: &str, handler: BuiltinHandler) {
+    register_kind(name, HandlerKind::Plain(handler));
+}
+
+/// 任意の種類のハンドラをグローバルディスパッチャに登録します。
+pub(crate) fn register_kind(name: &str, handler: HandlerKind) {
     if let Ok(mut h) = HANDLERS.lock() {
         // 同じ名前が既にあるかチェックして重複を防ぐ
-        if !h.iter().any(|(n, _)| *n == name) {
-            h.push((name, handler));
+        if !h.iter().any(|(n, _)| n == name) {
+            h.push((name.to_string(), handler));
         }
     }
 }
@@ -128,10 +699,278 @@ pub fn register_handler(name: &'static str, handler: BuiltinHandler) {
 /// Zshの`bridge_handler`から呼び出され、適切なRustハンドラ関数に処理を委譲します。
 /// ハンドラが見つからない場合は終了ステータス `1` を返します。
 pub fn dispatch(name: &str, args: &[&str]) -> i32 {
-    if let Ok(h_list) = HANDLERS.lock()
-        && let Some((_, h)) = h_list.iter().find(|(n, _)| *n == name)
+    dispatch_with_options(name, args, &BuiltinOptions { raw: None })
+}
+
+/// 指定された名前のビルトインハンドラを、解析済みのオプションと共に実行します。
+///
+/// オプションを受け取らないハンドラには `opts` は渡されません。
+/// ハンドラが見つからない場合は終了ステータス `1` を返します。
+pub fn dispatch_with_options(name: &str, args: &[&str], opts: &BuiltinOptions) -> i32 {
+    let raw: Vec<Vec<u8>> = args.iter().map(|a| a.as_bytes().to_vec()).collect();
+    dispatch_bytes(name, &raw, opts)
+}
+
+/// アンメタファイ済みの引数を各形式のビューに変換し、ハンドラを実行します。
+fn dispatch_bytes(name: &str, raw: &[Vec<u8>], opts: &BuiltinOptions) -> i32 {
+    let lossy: Vec<String> = raw
+        .iter()
+        .map(|a| String::from_utf8_lossy(a).into_owned())
+        .collect();
+    let args: Vec<&str> = lossy.iter().map(String::as_str).collect();
+    let bytes: Vec<&[u8]> = raw.iter().map(Vec::as_slice).collect();
+    let os: Vec<&OsStr> = bytes.iter().map(|b| OsStr::from_bytes(b)).collect();
+    let args = &args[..];
+
+    // ヘルプの要求と、Rust側で検査する引数の数を処理する
+    if let Some(help) = help_for(name)
+        && let Some(status) = handle_help(&help, raw, opts.is_set('h'))
     {
-        return h(name, args);
+        return status;
+    }
+
+    // ハンドラ内から他のビルトインを呼び出せるよう、複製してからロックを解放する
+    let handler = HANDLERS.lock().ok().and_then(|h_list| {
+        h_list
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, h)| h.clone())
+    });
+    match handler {
+        Some(HandlerKind::Plain(h)) => h(name, args),
+        Some(HandlerKind::WithOptions(h)) => h(name, args, opts),
+        Some(HandlerKind::Closure(f)) => {
+            let mut ctx = BuiltinCtx {
+                name,
+                args,
+                bytes: &bytes,
+                os: &os,
+                opts,
+            };
+            // 同じクロージャが再帰的に呼び出された場合はデッドロックを避けて失敗させる
+            match f.try_lock() {
+                Ok(mut f) => f(&mut ctx),
+                Err(_) => {
+                    crate::error_named(name, "builtin called recursively");
+                    1
+                }
+            }
+        }
+        None => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    /// `dispatch_bytes` と同じ方法で各形式のビューを作成し、`f` に渡します。
+    fn with_ctx<R>(raw: &[&[u8]], f: impl FnOnce(&BuiltinCtx) -> R) -> R {
+        let lossy: Vec<String> = raw
+            .iter()
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect();
+        let args: Vec<&str> = lossy.iter().map(String::as_str).collect();
+        let os: Vec<&OsStr> = raw.iter().map(|b| OsStr::from_bytes(b)).collect();
+        let opts = BuiltinOptions { raw: None };
+        let ctx = BuiltinCtx {
+            name: "test",
+            args: &args,
+            bytes: raw,
+            os: &os,
+            opts: &opts,
+        };
+        f(&ctx)
+    }
+
+    #[test]
+    fn args_strict_accepts_utf8() {
+        with_ctx(&[b"abc", "日本".as_bytes()], |ctx| {
+            assert_eq!(ctx.args_strict().unwrap(), vec!["abc", "日本"]);
+        });
+    }
+
+    #[test]
+    fn args_strict_reports_index_of_invalid_argument() {
+        with_ctx(&[b"ok", b"caf\xe9", b"\xff"], |ctx| {
+            match ctx.args_strict() {
+                Err(BuiltinArgError::InvalidUtf8 { index, lossy }) => {
+                    assert_eq!(index, 1);
+                    assert_eq!(lossy, "caf\u{fffd}");
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn latin1_bytes_are_kept_in_bytes_and_os_views() {
+        with_ctx(&[b"caf\xe9"], |ctx| {
+            assert_eq!(ctx.args(), ["caf\u{fffd}"]);
+            assert_eq!(ctx.args_bytes(), [b"caf\xe9".as_slice()]);
+            assert_eq!(ctx.args_os()[0].as_bytes(), b"caf\xe9");
+        });
+    }
+
+    /// `ind` の値を設定した `options` 構造体を作成します。
+    fn options(ind: &[(char, u8)], args: &mut [*mut c_char]) -> bindings::options {
+        let mut ops = bindings::options::default();
+        for &(c, value) in ind {
+            ops.ind[c as usize] = value;
+        }
+        ops.args = args.as_mut_ptr();
+        ops.argscount = args.len() as i32;
+        ops
+    }
+
+    #[test]
+    fn options_decode_minus_and_plus_bits() {
+        let mut ops = options(&[('a', 1), ('b', 2), ('c', 3)], &mut []);
+        let opts = unsafe { BuiltinOptions::from_raw(&mut ops) };
+        assert!(opts.is_set('a') && opts.is_minus('a') && !opts.is_plus('a'));
+        assert!(opts.is_set('b') && !opts.is_minus('b') && opts.is_plus('b'));
+        assert!(opts.is_minus('c') && opts.is_plus('c'));
+        assert!(!opts.is_set('d') && !opts.is_minus('d') && !opts.is_plus('d'));
+        // 引数を持たないオプション
+        assert_eq!(opts.arg('a'), None);
+        assert_eq!(opts.arg('c'), None);
+        // `ind` の範囲外の文字は未設定として扱う
+        assert!(!opts.is_set('日'));
+    }
+
+    #[test]
+    fn options_arg_uses_upper_bits_as_index() {
+        let first = CString::new("one").unwrap();
+        let second = CString::new("two").unwrap();
+        let mut args = [
+            first.as_ptr() as *mut c_char,
+            second.as_ptr() as *mut c_char,
+        ];
+        // 上位ビットは `args` のインデックス+1
+        let mut ops = options(
+            &[
+                ('v', (1 << 2) | 1),
+                ('o', (2 << 2) | 1),
+                ('x', (3 << 2) | 1),
+            ],
+            &mut args,
+        );
+        let opts = unsafe { BuiltinOptions::from_raw(&mut ops) };
+        assert_eq!(opts.arg('v').as_deref(), Some("one"));
+        assert_eq!(opts.arg('o').as_deref(), Some("two"));
+        assert!(opts.is_minus('o'));
+        // `argscount` を超えるインデックスは無視する
+        assert_eq!(opts.arg('x'), None);
+    }
+
+    #[test]
+    fn options_without_raw_struct_are_unset() {
+        let opts = unsafe { BuiltinOptions::from_raw(std::ptr::null_mut()) };
+        assert!(!opts.is_set('a'));
+        assert_eq!(opts.arg('a'), None);
+    }
+
+    // --- `ZString` が使用するZshのメモリ管理関数のスタブ ---
+    mod test_stubs {
+        use std::os::raw::{c_char, c_void};
+
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn zsfree(ptr: *mut c_void) {
+            if !ptr.is_null() {
+                unsafe { libc::free(ptr) }
+            }
+        }
+
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn ztrdup(s: *const c_char) -> *mut c_char {
+            if s.is_null() {
+                return std::ptr::null_mut();
+            }
+            unsafe { libc::strdup(s) }
+        }
+    }
+
+    fn noop(_name: &str, _args: &[&str]) -> i32 {
+        0
+    }
+
+    fn noop_opts(_name: &str, _args: &[&str], _opts: &BuiltinOptions) -> i32 {
+        0
+    }
+
+    fn optstr(builtin: &Builtin) -> Option<String> {
+        builtin.optstr.as_ref().map(|s| {
+            unsafe { std::ffi::CStr::from_ptr(s.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    #[test]
+    fn spec_without_help_keeps_zsh_argument_limits() {
+        let flags = BuiltinFlags::SKIP_DASH | BuiltinFlags::DASHDASH_VALID;
+        let builtin = Builtin::from(
+            BuiltinSpec::with_options("plain", "ab:", noop_opts)
+                .min_args(1)
+                .max_args(2)
+                .flags(flags),
+        );
+        assert_eq!(builtin.name(), "plain");
+        assert_eq!(builtin.min_args, 1);
+        assert_eq!(builtin.max_args, 2);
+        assert_eq!(builtin.flags, flags);
+        assert_eq!(optstr(&builtin).as_deref(), Some("ab:"));
+        assert_eq!(builtin.completion().optstr.as_deref(), Some("ab:"));
+        assert!(!builtin.completion().long_help);
+    }
+
+    #[test]
+    fn spec_with_help_adds_h_option_and_checks_count_in_rust() {
+        let builtin = Builtin::from(
+            BuiltinSpec::with_options("greet", "lv:", noop_opts)
+                .min_args(1)
+                .max_args(3)
+                .flags(BuiltinFlags::SKIP_DASH)
+                .description("Print a greeting.")
+                .option_help('l', "use the long form"),
+        );
+        // Zsh側の検査は無効にし、`handle_help` で検査する
+        assert_eq!(builtin.min_args, 0);
+        assert_eq!(builtin.max_args, -1);
+        assert_eq!(builtin.flags, BuiltinFlags::SKIP_DASH);
+        assert_eq!(optstr(&builtin).as_deref(), Some("lv:h"));
+
+        let completion = builtin.completion();
+        assert_eq!(completion.optstr.as_deref(), Some("lv:h"));
+        assert_eq!(
+            completion.option_help.last(),
+            Some(&('h', "show help".to_string()))
+        );
+        assert!(!completion.long_help);
+    }
+
+    #[test]
+    fn spec_with_help_and_no_options_uses_long_help() {
+        let builtin = Builtin::from(
+            BuiltinSpec::new("hello", noop)
+                .max_args(1)
+                .description("Say hello."),
+        );
+        assert_eq!(builtin.min_args, 0);
+        assert_eq!(builtin.max_args, -1);
+        assert_eq!(optstr(&builtin), None);
+        assert!(builtin.completion().long_help);
+    }
+
+    #[test]
+    fn spec_declaring_h_has_no_help() {
+        let builtin = Builtin::from(
+            BuiltinSpec::with_options("hist", "h", noop_opts)
+                .max_args(0)
+                .description("ignored"),
+        );
+        assert_eq!(builtin.max_args, 0);
+        assert_eq!(optstr(&builtin).as_deref(), Some("h"));
     }
-    1
 }
@@ -0,0 +1,314 @@
+//! このモジュールは、ビルトインコマンドの `_arguments` ベースの補完関数を生成する機能を提供します。
+//!
+//! 補完関数はオプション文字列、[`BuiltinSpec::option_help`](crate::BuiltinSpec::option_help)、
+//! [`BuiltinSpec::arg`](crate::BuiltinSpec::arg) などの宣言から生成されるため、
+//! Rustのコードと補完が食い違うことはありません。
+//!
+//! 生成した補完関数は、[`Features::add_completion_builtin`](crate::Features::add_completion_builtin)
+//! で追加したビルトインから `fpath` のディレクトリに書き出すか、
+//! [`Features::install_completions_at_boot`](crate::Features::install_completions_at_boot)
+//! によってモジュールの `boot` 時に `compdef` で登録できます。
+use crate::module::usage::parse_optstr;
+use std::sync::Mutex;
+
+/// 位置引数の補完方法。
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ArgCompletion {
+    /// 候補を表示せず、引数の説明のみを表示します。
+    Message,
+    /// Zshの既定の補完 (`_default`) を使用します。
+    #[default]
+    Default,
+    /// ファイル名を補完します (`_files`)。
+    Files,
+    /// ディレクトリ名を補完します (`_files -/`)。
+    Directories,
+    /// コマンド名を補完します (`_command_names`)。
+    Commands,
+    /// パラメータ名を補完します (`_parameters`)。
+    Parameters,
+    /// 固定の候補から補完します。
+    Values(Vec<String>),
+    /// `_arguments` のアクションをそのまま指定します (例: `"_users"`)。
+    Action(String),
+}
+
+impl ArgCompletion {
+    /// `_arguments` のアクション部分を返します。
+    fn action(&self) -> String {
+        match self {
+            ArgCompletion::Message => " ".to_string(),
+            ArgCompletion::Default => "_default".to_string(),
+            ArgCompletion::Files => "_files".to_string(),
+            ArgCompletion::Directories => "_files -/".to_string(),
+            ArgCompletion::Commands => "_command_names".to_string(),
+            ArgCompletion::Parameters => "_parameters".to_string(),
+            ArgCompletion::Values(values) => {
+                let values: Vec<String> = values.iter().map(|v| escape_value(v)).collect();
+                format!("({})", values.join(" "))
+            }
+            ArgCompletion::Action(action) => action.clone(),
+        }
+    }
+}
+
+/// ビルトインの補完関数の生成に必要な情報。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompletionSpec {
+    pub(crate) name: String,
+    pub(crate) optstr: Option<String>,
+    pub(crate) option_help: Vec<(char, String)>,
+    pub(crate) args: Vec<(String, ArgCompletion)>,
+    pub(crate) rest: Option<(String, ArgCompletion)>,
+    /// `--help` をオプションとして補完するかどうか。
+    pub(crate) long_help: bool,
+}
+
+impl CompletionSpec {
+    /// 補完関数の名前 (`_name`) を返します。
+    pub(crate) fn function_name(&self) -> String {
+        format!("_{}", self.name)
+    }
+
+    /// オプションや引数が宣言されているかどうかを返します。
+    ///
+    /// 何も宣言されていないビルトインの補完関数は生成しません。生成すると、
+    /// Zshの既定の補完が何も補完しない関数で置き換えられてしまうためです。
+    pub(crate) fn is_declared(&self) -> bool {
+        self.optstr.is_some() || !self.args.is_empty() || self.rest.is_some()
+    }
+
+    /// `_arguments` の呼び出しを返します。
+    ///
+    /// 位置引数が宣言されていない場合は、オプション以外の引数をZshの既定の補完で補完します。
+    pub(crate) fn arguments_call(&self) -> String {
+        let mut specs = Vec::new();
+        for (c, takes_arg) in parse_optstr(self.optstr.as_deref().unwrap_or("")) {
+            let help = self
+                .option_help
+                .iter()
+                .find(|(o, _)| *o == c)
+                .map(|(_, h)| escape_description(h))
+                .unwrap_or_default();
+            if takes_arg {
+                specs.push(format!("-{}+[{}]:arg: ", c, help));
+            } else {
+                specs.push(format!("-{}[{}]", c, help));
+            }
+        }
+        if self.long_help {
+            specs.push("(- *)--help[show help]".to_string());
+        }
+        for (i, (name, completion)) in self.args.iter().enumerate() {
+            specs.push(format!(
+                "{}:{}:{}",
+                i + 1,
+                escape_message(name),
+                completion.action()
+            ));
+        }
+        if let Some((name, completion)) = &self.rest {
+            specs.push(format!(
+                "*:{}:{}",
+                escape_message(name),
+                completion.action()
+            ));
+        }
+        if self.args.is_empty() && self.rest.is_none() {
+            specs.push("*:: :_default".to_string());
+        }
+
+        let mut call = String::from("_arguments -s -S");
+        for spec in specs {
+            call.push_str(" \\\n  ");
+            call.push_str(&quote(&spec));
+        }
+        call
+    }
+
+    /// `fpath` のディレクトリに置く補完関数ファイルの内容を返します。
+    pub(crate) fn autoload_file(&self) -> String {
+        format!("#compdef {}\n\n{}\n", self.name, self.arguments_call())
+    }
+
+    /// 補完関数を定義し、`compdef` で登録するスクリプトを返します。
+    ///
+    /// `compinit` が実行されていない場合は関数の定義のみを行います。
+    pub(crate) fn compdef_script(&self) -> String {
+        let function = self.function_name();
+        format!(
+            "{function}() {{\n{}\n}}\n(( $+functions[compdef] )) && compdef {function} {}\n",
+            self.arguments_call(),
+            self.name,
+        )
+    }
+}
+
+/// 文字列をシェルのシングルクォートで囲みます。
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// オプションの説明 (`[...]` の中) で特別な意味を持つ文字をエスケープします。
+fn escape_description(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('[', r"\[")
+        .replace(']', r"\]")
+        .replace(':', r"\:")
+}
+
+/// 位置引数の説明で特別な意味を持つ文字をエスケープします。
+fn escape_message(s: &str) -> String {
+    s.replace('\\', r"\\").replace(':', r"\:")
+}
+
+/// 固定の候補で特別な意味を持つ文字をエスケープします。
+fn escape_value(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if c.is_whitespace() || "\\()".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// `Features` に追加されたビルトインの補完情報。
+///
+/// 補完関数を書き出すビルトインが、実行時に全てのビルトインを参照できるようにするために使用します。
+static COMPLETIONS: Mutex<Vec<CompletionSpec>> = Mutex::new(Vec::new());
+
+/// ビルトインの補完情報を登録します。
+///
+/// オプションや引数が宣言されていないビルトインと、同じ名前のビルトインが既に
+/// 登録されている場合は登録されません。
+pub(crate) fn register_completion(spec: &CompletionSpec) {
+    if !spec.is_declared() {
+        return;
+    }
+    if let Ok(mut c) = COMPLETIONS.lock()
+        && !c.iter().any(|s| s.name == spec.name)
+    {
+        c.push(spec.clone());
+    }
+}
+
+/// 登録されたビルトインの補完情報をすべて返します。
+pub(crate) fn registered_completions() -> Vec<CompletionSpec> {
+    COMPLETIONS.lock().map(|c| c.clone()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(name: &str) -> CompletionSpec {
+        CompletionSpec {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn quote_escapes_single_quotes() {
+        assert_eq!(quote("abc"), "'abc'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape_description(r"a[b]:c\"), r"a\[b\]\:c\\");
+        assert_eq!(escape_message(r"dir:[x]\"), r"dir\:[x]\\");
+        assert_eq!(escape_value(r"a b(c)\"), r"a\ b\(c\)\\");
+    }
+
+    #[test]
+    fn arguments_call_from_options_and_args() {
+        let spec = CompletionSpec {
+            optstr: Some("lv:".to_string()),
+            option_help: vec![('l', "use [long] form".to_string())],
+            args: vec![
+                ("name".to_string(), ArgCompletion::Message),
+                (
+                    "mode".to_string(),
+                    ArgCompletion::Values(vec!["fast".to_string(), "very slow".to_string()]),
+                ),
+            ],
+            rest: Some(("files".to_string(), ArgCompletion::Files)),
+            ..spec("greet")
+        };
+        assert_eq!(
+            spec.arguments_call(),
+            "_arguments -s -S \\\n  \
+             '-l[use \\[long\\] form]' \\\n  \
+             '-v+[]:arg: ' \\\n  \
+             '1:name: ' \\\n  \
+             '2:mode:(fast very\\ slow)' \\\n  \
+             '*:files:_files'"
+        );
+    }
+
+    #[test]
+    fn arguments_call_falls_back_to_default_for_operands() {
+        let spec = CompletionSpec {
+            optstr: Some("a".to_string()),
+            long_help: false,
+            ..spec("f")
+        };
+        assert_eq!(
+            spec.arguments_call(),
+            "_arguments -s -S \\\n  '-a[]' \\\n  '*:: :_default'"
+        );
+    }
+
+    #[test]
+    fn arguments_call_with_long_help() {
+        let spec = CompletionSpec {
+            long_help: true,
+            args: vec![("dir".to_string(), ArgCompletion::Directories)],
+            ..spec("f")
+        };
+        assert_eq!(
+            spec.arguments_call(),
+            "_arguments -s -S \\\n  '(- *)--help[show help]' \\\n  '1:dir:_files -/'"
+        );
+    }
+
+    #[test]
+    fn undeclared_builtins_have_no_completion() {
+        assert!(!spec("f").is_declared());
+        assert!(
+            CompletionSpec {
+                optstr: Some(String::new()),
+                ..spec("f")
+            }
+            .is_declared()
+        );
+        assert!(
+            CompletionSpec {
+                rest: Some(("args".to_string(), ArgCompletion::Default)),
+                ..spec("f")
+            }
+            .is_declared()
+        );
+    }
+
+    #[test]
+    fn generated_files_and_scripts() {
+        let spec = CompletionSpec {
+            args: vec![("name".to_string(), ArgCompletion::Default)],
+            ..spec("greet")
+        };
+        assert_eq!(spec.function_name(), "_greet");
+        assert_eq!(
+            spec.autoload_file(),
+            "#compdef greet\n\n_arguments -s -S \\\n  '1:name:_default'\n"
+        );
+        assert_eq!(
+            spec.compdef_script(),
+            "_greet() {\n_arguments -s -S \\\n  '1:name:_default'\n}\n\
+             (( $+functions[compdef] )) && compdef _greet greet\n"
+        );
+    }
+}
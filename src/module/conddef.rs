@@ -2,8 +2,47 @@
 //!
 //! Zshのモジュールはカスタムの条件式を登録でき、これによりZshスクリプト内で
 //! `if [[ ... ]]` の形式で利用可能な新しい条件を導入することができます。
+//!
+//! 条件式は [`Conddef::prefix`] (`[[ -mytest arg ]]`) または [`Conddef::infix`]
+//! (`[[ lhs -matches rhs ]]`) で定義し、[`Features::add_cond`](crate::Features::add_cond)
+//! で登録します。
+//!
+//! ```ignore
+//! fn is_even(args: &[&str]) -> bool {
+//!     args[0].parse::<i64>().is_ok_and(|n| n % 2 == 0)
+//! }
+//!
+//! Features::new().add_cond(Conddef::prefix("-even", is_even))
+//! ```
+//!
+//! オペランドが不正な場合など、エラーとして扱いたい場合は [`Conddef::prefix_fallible`]
+//! などで [`CondResult`] を返すハンドラを使用します。`Err` はZshのエラーとして表示され、
+//! 条件式を含むコマンドの実行は中断されます。
 use crate::ZString;
-use crate::bindings::{CondHandler, conddef};
+use crate::bindings::{self, CondHandler, conddef};
+use crate::envs::unmetafy_bytes;
+use crate::output::error_named;
+use std::error::Error;
+use std::os::raw::{c_char, c_int};
+use std::sync::Mutex;
+
+/// 条件式の判定を行うRustのハンドラ関数の型。
+///
+/// 引数には展開済みのオペランドが渡されます。条件が真の場合は `true` を返します。
+pub type CondHandlerFn = fn(&[&str]) -> bool;
+
+/// エラーを返す可能性のある条件式のハンドラの結果。
+pub type CondResult = Result<bool, Box<dyn Error>>;
+
+/// エラーを返す可能性のある条件式のRustのハンドラ関数の型。
+pub type CondResultFn = fn(&[&str]) -> CondResult;
+
+/// 登録された条件式のハンドラ。
+#[derive(Clone, Copy)]
+pub(crate) enum CondKind {
+    Plain(CondHandlerFn),
+    Fallible(CondResultFn),
+}
 
 /// Zshの条件式定義をカプセル化する構造体。
 ///
@@ -15,10 +54,86 @@ pub struct Conddef {
     handler: CondHandler, // ZshのC関数ポインタ
     min: i32,
     max: i32,
+    condid: i32,
     module: Option<ZString>, // モジュール名 (オプション)
+    kind: Option<CondKind>,  // Rustのハンドラ (`Features::add_cond` で登録される)
 }
 
 impl Conddef {
+    /// `[[ -name arg ... ]]` の形式の前置条件式を定義します。
+    ///
+    /// `name` の先頭の `-` は省略可能です。既定ではオペランドを1つだけ受け取ります。
+    pub fn prefix(name: &str, handler: CondHandlerFn) -> Self {
+        Self::with_kind(name, 0, 1, 1, CondKind::Plain(handler))
+    }
+
+    /// `[[ lhs -name rhs ]]` の形式の中置条件式を定義します。
+    ///
+    /// `name` の先頭の `-` は省略可能です。ハンドラには `[lhs, rhs]` が渡されます。
+    pub fn infix(name: &str, handler: CondHandlerFn) -> Self {
+        Self::with_kind(
+            name,
+            bindings::CONDF_INFIX as i32,
+            0,
+            0,
+            CondKind::Plain(handler),
+        )
+    }
+
+    /// `Result` を返すハンドラで前置条件式を定義します。
+    ///
+    /// `Err` を返した場合は `name: message` の形式でエラーが表示され、Zshのエラーフラグが設定されます。
+    pub fn prefix_fallible(name: &str, handler: CondResultFn) -> Self {
+        Self::with_kind(name, 0, 1, 1, CondKind::Fallible(handler))
+    }
+
+    /// `Result` を返すハンドラで中置条件式を定義します。
+    ///
+    /// `Err` を返した場合は `name: message` の形式でエラーが表示され、Zshのエラーフラグが設定されます。
+    pub fn infix_fallible(name: &str, handler: CondResultFn) -> Self {
+        Self::with_kind(
+            name,
+            bindings::CONDF_INFIX as i32,
+            0,
+            0,
+            CondKind::Fallible(handler),
+        )
+    }
+
+    fn with_kind(name: &str, flags: i32, min: i32, max: i32, kind: CondKind) -> Self {
+        Self {
+            // Zshは先頭の `-` を除いた名前で条件式を検索する
+            name: ZString::new(name.strip_prefix('-').unwrap_or(name)),
+            flags,
+            handler: Some(cond_trampoline),
+            min,
+            max,
+            condid: 0,
+            module: None,
+            kind: Some(kind),
+        }
+    }
+
+    /// 前置条件式が受け取るオペランドの最小数を設定します。
+    pub fn min_args(mut self, n: u32) -> Self {
+        self.min = n.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// 前置条件式が受け取るオペランドの最大数を設定します。
+    pub fn max_args(mut self, n: u32) -> Self {
+        self.max = n.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// Rustのハンドラをディスパッチャに登録し、その識別子を `condid` に設定します。
+    pub(crate) fn register(&mut self) {
+        if let Some(kind) = self.kind.take() {
+            let name = unsafe { std::ffi::CStr::from_ptr(self.name.as_ptr()) };
+            self.condid = register_cond(&name.to_string_lossy(), kind);
+        }
+    }
+
     /// `Conddef`インスタンスをZshの`conddef`構造体として表現します。
     ///
     /// この生構造体はZshのモジュールAPIに渡され、条件式として登録されます。
@@ -33,6 +148,7 @@ impl Conddef {
             handler: self.handler,
             min: self.min,
             max: self.max,
+            condid: self.condid,
             module: self
                 .module
                 .as_ref()
@@ -41,3 +157,108 @@ impl Conddef {
         }
     }
 }
+
+/// 登録された条件式のハンドラ。`condid` をインデックスとして参照します。
+/// (条件式の名前, `CondKind`) のタプルを格納します。
+static COND_HANDLERS: Mutex<Vec<(String, CondKind)>> = Mutex::new(Vec::new());
+
+/// 条件式のハンドラを登録し、その `condid` を返します。
+fn register_cond(name: &str, kind: CondKind) -> i32 {
+    let mut handlers = COND_HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+    handlers.push((format!("-{}", name), kind));
+    (handlers.len() - 1) as i32
+}
+
+/// Zshから直接呼ばれる条件式のブリッジ関数。
+///
+/// `condid` に対応するRustのハンドラを探し、`cond_str` で展開したオペランドを渡して実行します。
+///
+/// # Safety
+/// `args` はZshが渡すNULL終端のオペランドの配列である必要があります。
+unsafe extern "C" fn cond_trampoline(args: *mut *mut c_char, id: c_int) -> c_int {
+    // ハンドラ内から他の条件式を評価できるよう、複製してからロックを解放する
+    let kind = COND_HANDLERS
+        .lock()
+        .ok()
+        .and_then(|h| h.get(id as usize).cloned());
+    let Some((name, kind)) = kind else {
+        return 0;
+    };
+
+    let mut operands = Vec::new();
+    unsafe {
+        while !args.is_null() && !(*args.add(operands.len())).is_null() {
+            let s = bindings::cond_str(args, operands.len() as c_int, 0);
+            operands.push(String::from_utf8_lossy(&unmetafy_bytes(s)).into_owned());
+        }
+    }
+
+    match eval_cond(kind, &operands) {
+        Ok(result) => result,
+        Err(message) => {
+            // zerrnam がエラーフラグを設定し、条件式を含むコマンドの実行を中断させる
+            error_named(&name, &message);
+            0
+        }
+    }
+}
+
+/// 展開済みのオペランドでハンドラを実行し、Zshに返す値 (真なら `1`) に変換します。
+///
+/// `Fallible` のハンドラが `Err` を返した場合は、表示するエラーメッセージを返します。
+fn eval_cond(kind: CondKind, operands: &[String]) -> Result<c_int, String> {
+    let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+    match kind {
+        CondKind::Plain(handler) => Ok(handler(&operands) as c_int),
+        CondKind::Fallible(handler) => handler(&operands)
+            .map(|result| result as c_int)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operands(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn is_even(args: &[&str]) -> bool {
+        args[0].parse::<i64>().is_ok_and(|n| n % 2 == 0)
+    }
+
+    fn same(args: &[&str]) -> bool {
+        args.len() == 2 && args[0] == args[1]
+    }
+
+    fn positive(args: &[&str]) -> CondResult {
+        let n: i64 = args[0].parse()?;
+        Ok(n > 0)
+    }
+
+    #[test]
+    fn plain_handler_result_maps_to_zsh_status() {
+        let kind = CondKind::Plain(is_even);
+        assert_eq!(eval_cond(kind, &operands(&["4"])), Ok(1));
+        assert_eq!(eval_cond(kind, &operands(&["3"])), Ok(0));
+    }
+
+    #[test]
+    fn infix_handler_receives_both_operands() {
+        let kind = CondKind::Plain(same);
+        assert_eq!(eval_cond(kind, &operands(&["a", "a"])), Ok(1));
+        assert_eq!(eval_cond(kind, &operands(&["a", "b"])), Ok(0));
+    }
+
+    #[test]
+    fn fallible_handler_maps_err_to_message() {
+        let kind = CondKind::Fallible(positive);
+        assert_eq!(eval_cond(kind, &operands(&["5"])), Ok(1));
+        assert_eq!(eval_cond(kind, &operands(&["-5"])), Ok(0));
+        assert_eq!(
+            eval_cond(kind, &operands(&["five"])),
+            Err("invalid digit found in string".to_string())
+        );
+    }
+}
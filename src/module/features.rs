@@ -3,8 +3,11 @@
 //!
 //! `Features` 構造体は、これらの機能のRust表現を保持し、必要に応じてZshのC構造体に変換します。
 use crate::bindings;
-use crate::module::builtin::BuiltinHandler;
-use crate::module::{Builtin, Conddef, Mathfunc, Paramdef};
+use crate::module::builtin::{
+    BuiltinCtx, BuiltinHandler, BuiltinOptsHandler, BuiltinSpec, HandlerKind,
+};
+use crate::module::completion::{register_completion, registered_completions};
+use crate::module::{ArgCompletion, Builtin, Conddef, Mathfunc, ModuleInstance, Paramdef};
 
 /// Zshの `features` 構造体を安全に構築・保持するためのラッパー。
 ///
@@ -16,6 +19,8 @@ pub struct Features {
     math_funcs: Vec<Mathfunc>,
     param_defs: Vec<Paramdef>,
     n_abstract: i32,
+    /// `boot` 時にビルトインの補完関数を `compdef` で登録するかどうか。
+    install_completions: bool,
 
     /// Zshに渡すポインタの参照先を保持するためのキャッシュ。
     /// `as_zsh_features`内で作成される一時的な`Vec`が即座に解放されるのを防ぎ、
@@ -50,6 +55,7 @@ impl Features {
             math_funcs: Vec::new(),
             param_defs: Vec::new(),
             n_abstract: 0,
+            install_completions: false,
             raw_builtins: Vec::new(),
             raw_conddefs: Vec::new(),
             raw_mathfuncs: Vec::new(),
@@ -68,12 +74,241 @@ impl Features {
         register_handler(name, handler);
 
         // 2. ビルトイン定義を追加
-        self.builtins.push(Builtin::new(name, handler));
+        self.push_builtin(Builtin::new(name, handler));
+        self
+    }
+
+    /// オプション文字列を宣言したビルトインコマンドを `Features` に追加します。
+    ///
+    /// `optstr` (例: `"ab:c"`) に従ってZshがオプションを解析し、
+    /// その結果が `BuiltinOptions` としてハンドラに渡されます。
+    pub fn add_builtin_with_options(
+        mut self,
+        name: &'static str,
+        optstr: &str,
+        handler: BuiltinOptsHandler,
+    ) -> Self {
+        use crate::module::builtin::{Builtin, register_kind};
+
+        let builtin = Builtin::with_options(name, optstr, handler);
+        register_kind(name, builtin.handler_kind());
+        self.push_builtin(builtin);
+        self
+    }
+
+    /// 状態を持つクロージャをハンドラとするビルトインコマンドを `Features` に追加します。
+    ///
+    /// ```ignore
+    /// let mut count = 0;
+    /// Features::new().add_builtin_closure("count", move |ctx| {
+    ///     count += ctx.args().len();
+    ///     println!("{}", count);
+    ///     0
+    /// })
+    /// ```
+    pub fn add_builtin_closure(
+        self,
+        name: &str,
+        handler: impl FnMut(&mut BuiltinCtx) -> i32 + Send + 'static,
+    ) -> Self {
+        self.add_builtin_kind(name, HandlerKind::closure(handler))
+    }
+
+    /// `export_module!` で登録したモジュールのインスタンスを受け取るビルトインコマンドを追加します。
+    ///
+    /// ハンドラにはモジュールの実体が `&mut M` として渡されるため、
+    /// 別のグローバル変数を用意せずにモジュールの状態を読み書きできます。
+    /// ハンドラの実行中はモジュールのインスタンスがロックされます。ハンドラの中や
+    /// モジュールの `boot` の中から同じモジュールのこの種のビルトインを呼び出した場合は、
+    /// デッドロックを避けてエラーを表示し、終了ステータス `1` を返します。
+    ///
+    /// ```ignore
+    /// impl ZshModule for MyModule {
+    ///     fn features(&self) -> Features {
+    ///         Features::new().add_module_builtin("hits", |m: &mut MyModule, _ctx| {
+    ///             m.hits += 1;
+    ///             0
+    ///         })
+    ///     }
+    /// }
+    /// ```
+    pub fn add_module_builtin<M: ModuleInstance>(
+        self,
+        name: &str,
+        mut handler: impl FnMut(&mut M, &mut BuiltinCtx) -> i32 + Send + 'static,
+    ) -> Self {
+        self.add_builtin_kind(
+            name,
+            HandlerKind::closure(move |ctx| match M::try_with_instance(|m| handler(m, ctx)) {
+                Some(status) => status,
+                None => {
+                    crate::error_named(ctx.name(), "module instance is busy");
+                    1
+                }
+            }),
+        )
+    }
+
+    /// [`BuiltinSpec`] で引数の数やフラグを宣言したビルトインコマンドを追加します。
+    ///
+    /// ```ignore
+    /// Features::new().add_builtin_spec(BuiltinSpec::new("pair", pair).min_args(2).max_args(2))
+    /// ```
+    pub fn add_builtin_spec(mut self, spec: BuiltinSpec) -> Self {
+        use crate::module::builtin::register_kind;
+
+        let name = spec.name().to_string();
+        if spec.help_conflicts() {
+            crate::warn_named(
+                &name,
+                "option -h is declared by the builtin; help is disabled",
+            );
+        }
+        if let Some(help) = spec.help() {
+            crate::module::usage::register_help(help);
+        }
+        let builtin = Builtin::from(spec);
+        register_kind(&name, builtin.handler_kind());
+        self.push_builtin(builtin);
+        self
+    }
+
+    /// `#[zsh_builtin]` 属性で定義されたビルトインコマンドをすべて追加します。
+    ///
+    /// `export_module!` はモジュールのセットアップ時に自動的にこのメソッドを呼び出すため、
+    /// 通常は明示的に呼び出す必要はありません。既に同じ名前のビルトインがある場合は追加されません。
+    pub fn add_attribute_builtins(mut self) -> Self {
+        use crate::module::builtin::submitted_specs;
+
+        for spec in submitted_specs() {
+            if !self.builtins.iter().any(|b| b.name() == spec.name()) {
+                self = self.add_builtin_spec(spec);
+            }
+        }
+        self
+    }
+
+    /// 任意の種類のハンドラを持つビルトインコマンドを追加します。
+    fn add_builtin_kind(mut self, name: &str, handler: HandlerKind) -> Self {
+        use crate::module::builtin::register_kind;
+
+        register_kind(name, handler.clone());
+        self.push_builtin(Builtin::with_kind(name, handler, None));
+        self
+    }
+
+    /// ビルトイン定義を追加し、その補完情報を補完関数の生成用に登録します。
+    fn push_builtin(&mut self, builtin: Builtin) {
+        register_completion(builtin.completion());
+        self.builtins.push(builtin);
+    }
+
+    /// ビルトインコマンドの補完関数を、(ファイル名, 内容) の組として生成します。
+    ///
+    /// ファイル名は `_name` の形式で、内容は `#compdef name` で始まる `_arguments` ベースの関数です。
+    /// `fpath` に含まれるディレクトリに書き出すと、`compinit` によって自動的に読み込まれます。
+    /// オプションも引数も宣言していないビルトインは、Zshの既定の補完を使用するため含まれません。
+    pub fn completion_files(&self) -> Vec<(String, String)> {
+        self.builtins
+            .iter()
+            .map(|b| b.completion())
+            .filter(|completion| completion.is_declared())
+            .map(|completion| (completion.function_name(), completion.autoload_file()))
+            .collect()
+    }
+
+    /// 補完関数をディレクトリに書き出すビルトインコマンドを追加します。
+    ///
+    /// `name dir` を実行すると、モジュールが提供する全てのビルトインの補完関数が `dir` に書き出されます。
+    /// 対象のビルトインは実行時に決まるため、このメソッドの後に追加したビルトインや
+    /// `#[zsh_builtin]` で定義したビルトインも含まれます。
+    ///
+    /// ```ignore
+    /// Features::new()
+    ///     .add_builtin_spec(BuiltinSpec::new("greet", greet).arg("name", ArgCompletion::Default))
+    ///     .add_completion_builtin("mymod-gen-completions")
+    /// ```
+    /// ```zsh
+    /// mymod-gen-completions ~/.zsh/completions
+    /// ```
+    pub fn add_completion_builtin(self, name: &str) -> Self {
+        let spec = BuiltinSpec::closure(name, |ctx| {
+            let dir = std::path::Path::new(ctx.args_os()[0]);
+            for completion in registered_completions() {
+                let path = dir.join(completion.function_name());
+                let contents = completion.autoload_file();
+                if let Err(e) = std::fs::write(&path, contents) {
+                    crate::error_named(ctx.name(), &format!("{}: {}", path.display(), e));
+                    return 1;
+                }
+            }
+            0
+        })
+        .min_args(1)
+        .max_args(1)
+        .description("Write completion functions for the module's builtins to a directory.")
+        .arg("dir", ArgCompletion::Directories);
+        self.add_builtin_spec(spec)
+    }
+
+    /// モジュールの `boot` 時に、ビルトインの補完関数を定義して `compdef` で登録します。
+    ///
+    /// `compinit` の実行前にモジュールを読み込んだ場合、関数は定義されますが `compdef` は実行されません。
+    pub fn install_completions_at_boot(mut self) -> Self {
+        self.install_completions = true;
+        self
+    }
+
+    /// ビルトインの補完関数を定義し、`compdef` で登録します。
+    ///
+    /// オプションも引数も宣言していないビルトインは、Zshの既定の補完のまま残されます。
+    pub fn install_completions(&self) {
+        let script: String = self
+            .builtins
+            .iter()
+            .map(|b| b.completion())
+            .filter(|completion| completion.is_declared())
+            .map(|completion| completion.compdef_script())
+            .collect();
+        crate::shell::eval(&script);
+    }
+
+    /// `install_completions_at_boot` が指定されている場合に補完関数を登録します。
+    pub(crate) fn boot(&self) {
+        if self.install_completions {
+            self.install_completions();
+        }
+    }
+
+    /// 条件式定義を `Features` に追加します。
+    ///
+    /// `Conddef::prefix` などで作成した条件式のハンドラは、内部的にグローバルディスパッチャに登録されます。
+    pub fn add_cond(mut self, mut cond: Conddef) -> Self {
+        cond.register();
+        self.conddefs.push(cond);
+        self
+    }
+
+    /// 数式関数定義を `Features` に追加します。
+    ///
+    /// `Mathfunc::numeric` などで作成した数式関数のハンドラは、内部的にグローバルディスパッチャに登録されます。
+    pub fn add_math_func(mut self, mut func: Mathfunc) -> Self {
+        func.register();
+        self.math_funcs.push(func);
         self
     }
 
     /// パラメータ定義を `Features` に追加します。
-    pub fn add_param(mut self, param: Paramdef) -> Self {
+    ///
+    /// `Paramdef::scalar` などのビルダーで作成した特殊パラメータの場合、
+    /// そのハンドラは内部的にグローバルディスパッチャに登録されます。
+    pub fn add_param(mut self, param: impl Into<Paramdef>) -> Self {
+        use crate::module::paramdef::register_param;
+
+        let param = param.into();
+        if let Some((name, handlers)) = param.handlers() {
+            register_param(name, handlers);
+        }
         self.param_defs.push(param);
         self
     }
@@ -89,7 +324,14 @@ impl Features {
         self.raw_builtins = self.builtins.iter().map(|b| b.as_raw()).collect();
         self.raw_conddefs = self.conddefs.iter().map(|c| c.as_raw()).collect();
         self.raw_mathfuncs = self.math_funcs.iter().map(|m| m.as_raw()).collect();
+
+        // Zsh は `paramdef.pm` に作成したパラメータを記録し、無効化時にそれを参照して削除します。
+        // 再構築のたびに失われないよう、前回の値を引き継ぎます。
+        let old_pms: Vec<bindings::Param> = self.raw_paramdefs.iter().map(|p| p.pm).collect();
         self.raw_paramdefs = self.param_defs.iter().map(|p| p.as_raw()).collect();
+        for (raw, pm) in self.raw_paramdefs.iter_mut().zip(old_pms) {
+            raw.pm = pm;
+        }
 
         bindings::features {
             bn_list: self.raw_builtins.as_mut_ptr(),
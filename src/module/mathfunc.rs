@@ -2,8 +2,68 @@
 //!
 //! Zshのモジュールはカスタムの数式関数を登録でき、これによりZshスクリプト内で
 //! `(( result = my_math_func(arg) ))` のような形式で利用可能な新しい関数を導入することができます。
+//!
+//! 数式関数は [`Mathfunc::numeric`] (数値の引数) または [`Mathfunc::string`]
+//! (文字列の引数) で定義し、[`Features::add_math_func`](crate::Features::add_math_func)
+//! で登録します。
+//!
+//! ```ignore
+//! fn mymax(args: &[ZshNumber]) -> ZshNumber {
+//!     args.iter()
+//!         .copied()
+//!         .fold(ZshNumber::Int(0), |a, b| if b.as_f64() > a.as_f64() { b } else { a })
+//! }
+//!
+//! Features::new().add_math_func(Mathfunc::numeric("mymax", mymax).min_args(1))
+//! ```
+//!
+//! ゼロ除算や定義域の外の引数など、算術エラーを発生させたい場合は
+//! [`Mathfunc::numeric_fallible`] などで [`MathResult`] を返すハンドラを使用します。
+//! `Err` はZshのエラーとして表示され、それを含む `(( ))` の評価は中断されます。
+//!
+//! ```ignore
+//! fn mydiv(args: &[ZshNumber]) -> MathResult {
+//!     match (args[0], args[1]) {
+//!         (ZshNumber::Int(_), ZshNumber::Int(0)) => Err("division by zero".into()),
+//!         (ZshNumber::Int(a), ZshNumber::Int(b)) => Ok(ZshNumber::Int(a / b)),
+//!         (a, b) => Ok(ZshNumber::Float(a.as_f64() / b.as_f64())),
+//!     }
+//! }
+//! ```
 use crate::ZString;
-use crate::bindings::{NumMathFunc, StrMathFunc, mathfunc};
+use crate::ZshNumber;
+use crate::bindings::{self, NumMathFunc, StrMathFunc, mathfunc, mnumber};
+use crate::envs::unmetafy_bytes;
+use crate::output::error_named;
+use std::error::Error;
+use std::os::raw::{c_char, c_int};
+use std::sync::Mutex;
+
+/// 数値の引数を取る数式関数のRustのハンドラ関数の型。
+pub type NumMathHandler = fn(&[ZshNumber]) -> ZshNumber;
+
+/// 文字列の引数を取る数式関数のRustのハンドラ関数の型。
+///
+/// 引数には括弧の中の文字列が評価されずにそのまま渡されます。
+pub type StrMathHandler = fn(&str) -> ZshNumber;
+
+/// エラーを返す可能性のある数式関数のハンドラの結果。
+pub type MathResult = Result<ZshNumber, Box<dyn Error>>;
+
+/// エラーを返す可能性のある、数値の引数を取る数式関数のハンドラ関数の型。
+pub type NumMathResultHandler = fn(&[ZshNumber]) -> MathResult;
+
+/// エラーを返す可能性のある、文字列の引数を取る数式関数のハンドラ関数の型。
+pub type StrMathResultHandler = fn(&str) -> MathResult;
+
+/// 登録された数式関数のハンドラ。
+#[derive(Clone, Copy)]
+pub(crate) enum MathKind {
+    Num(NumMathHandler),
+    Str(StrMathHandler),
+    NumFallible(NumMathResultHandler),
+    StrFallible(StrMathResultHandler),
+}
 
 /// Zshの数式関数定義をカプセル化する構造体。
 ///
@@ -16,9 +76,82 @@ pub struct Mathfunc {
     sfunc: StrMathFunc, // 文字列引数を取るハンドラ関数ポインタ
     min_args: i32,
     max_args: i32,
+    funcid: i32,
+    kind: Option<MathKind>, // Rustのハンドラ (`Features::add_math_func` で登録される)
 }
 
 impl Mathfunc {
+    /// 数値の引数を取る数式関数 (`(( name(a, b, c) ))`) を定義します。
+    ///
+    /// 既定では任意の数の引数を受け取ります。引数は評価された値として渡されます。
+    pub fn numeric(name: &str, handler: NumMathHandler) -> Self {
+        Self::numeric_kind(name, MathKind::Num(handler))
+    }
+
+    /// `Result` を返すハンドラで、数値の引数を取る数式関数を定義します。
+    ///
+    /// `Err` を返した場合は `name: message` の形式でエラーが表示され、式の評価が中断されます。
+    pub fn numeric_fallible(name: &str, handler: NumMathResultHandler) -> Self {
+        Self::numeric_kind(name, MathKind::NumFallible(handler))
+    }
+
+    fn numeric_kind(name: &str, kind: MathKind) -> Self {
+        Self {
+            name: ZString::new(name),
+            flags: 0,
+            nfunc: Some(num_trampoline),
+            sfunc: None,
+            min_args: 0,
+            max_args: -1, // -1 means no maximum argument limit
+            funcid: 0,
+            kind: Some(kind),
+        }
+    }
+
+    /// 文字列の引数を取る数式関数 (`(( name(text) ))`) を定義します (`MFF_STR`)。
+    pub fn string(name: &str, handler: StrMathHandler) -> Self {
+        Self::string_kind(name, MathKind::Str(handler))
+    }
+
+    /// `Result` を返すハンドラで、文字列の引数を取る数式関数を定義します (`MFF_STR`)。
+    ///
+    /// `Err` を返した場合は `name: message` の形式でエラーが表示され、式の評価が中断されます。
+    pub fn string_fallible(name: &str, handler: StrMathResultHandler) -> Self {
+        Self::string_kind(name, MathKind::StrFallible(handler))
+    }
+
+    fn string_kind(name: &str, kind: MathKind) -> Self {
+        Self {
+            name: ZString::new(name),
+            flags: bindings::MFF_STR as i32,
+            nfunc: None,
+            sfunc: Some(str_trampoline),
+            min_args: 0,
+            max_args: 0,
+            funcid: 0,
+            kind: Some(kind),
+        }
+    }
+
+    /// 数値の引数の最小数を設定します。
+    pub fn min_args(mut self, n: u32) -> Self {
+        self.min_args = n.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// 数値の引数の最大数を設定します。既定では無制限です。
+    pub fn max_args(mut self, n: u32) -> Self {
+        self.max_args = n.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// Rustのハンドラをディスパッチャに登録し、その識別子を `funcid` に設定します。
+    pub(crate) fn register(&mut self) {
+        if let Some(kind) = self.kind.take() {
+            self.funcid = register_math(kind);
+        }
+    }
+
     /// `Mathfunc`インスタンスをZshの`mathfunc`構造体として表現します。
     ///
     /// この生構造体はZshのモジュールAPIに渡され、数式関数として登録されます。
@@ -34,7 +167,220 @@ impl Mathfunc {
             sfunc: self.sfunc,
             minargs: self.min_args,
             maxargs: self.max_args,
+            funcid: self.funcid,
             ..unsafe { std::mem::zeroed() }
         }
     }
 }
+
+/// 登録された数式関数のハンドラ。`funcid` をインデックスとして参照します。
+static MATH_HANDLERS: Mutex<Vec<MathKind>> = Mutex::new(Vec::new());
+
+/// 数式関数のハンドラを登録し、その `funcid` を返します。
+fn register_math(kind: MathKind) -> i32 {
+    let mut handlers = MATH_HANDLERS.lock().unwrap_or_else(|e| e.into_inner());
+    handlers.push(kind);
+    (handlers.len() - 1) as i32
+}
+
+/// `funcid` に対応するハンドラを返します。
+///
+/// ハンドラ内から他の数式関数を評価できるよう、複製してからロックを解放します。
+fn math_handler(id: c_int) -> Option<MathKind> {
+    MATH_HANDLERS
+        .lock()
+        .ok()
+        .and_then(|h| h.get(id as usize).copied())
+}
+
+/// ハンドラの結果をZshの `mnumber` に変換します。
+///
+/// `Err` の場合は `report` でエラーを表示して `0` を返します。`zerrnam` で表示すると
+/// エラーフラグが設定され、式の評価は中断されます。
+fn math_result(result: Result<ZshNumber, String>, report: impl FnOnce(&str)) -> mnumber {
+    match result {
+        Ok(n) => n.into_raw(),
+        Err(message) => {
+            report(&message);
+            ZshNumber::Int(0).into_raw()
+        }
+    }
+}
+
+/// 数式関数の名前を `name: message` の形式でエラーを表示します。
+fn report_error(name: *mut c_char, message: &str) {
+    let name = if name.is_null() {
+        String::new()
+    } else {
+        String::from_utf8_lossy(&unsafe { unmetafy_bytes(name) }).into_owned()
+    };
+    error_named(&name, message);
+}
+
+/// Zshから渡された `argc` 個の `mnumber` を `ZshNumber` に変換します。
+///
+/// # Safety
+/// `argv` は `argc` 個の `mnumber` を指している必要があります。
+unsafe fn num_args(argc: c_int, argv: *const mnumber) -> Vec<ZshNumber> {
+    (0..argc.max(0) as usize)
+        .map(|i| ZshNumber::from_raw(unsafe { *argv.add(i) }))
+        .collect()
+}
+
+/// 数値の引数を取るハンドラを実行します。`Err` はエラーメッセージに変換します。
+///
+/// ハンドラが見つからない場合は `0` を返します。
+fn eval_num(kind: Option<MathKind>, args: &[ZshNumber]) -> Result<ZshNumber, String> {
+    match kind {
+        Some(MathKind::Num(handler)) => Ok(handler(args)),
+        Some(MathKind::NumFallible(handler)) => handler(args).map_err(|e| e.to_string()),
+        _ => Ok(ZshNumber::Int(0)),
+    }
+}
+
+/// 文字列の引数を取るハンドラを実行します。`Err` はエラーメッセージに変換します。
+///
+/// ハンドラが見つからない場合は `0` を返します。
+fn eval_str(kind: Option<MathKind>, arg: &str) -> Result<ZshNumber, String> {
+    match kind {
+        Some(MathKind::Str(handler)) => Ok(handler(arg)),
+        Some(MathKind::StrFallible(handler)) => handler(arg).map_err(|e| e.to_string()),
+        _ => Ok(ZshNumber::Int(0)),
+    }
+}
+
+/// Zshから直接呼ばれる、数値の引数を取る数式関数のブリッジ関数。
+///
+/// # Safety
+/// `argv` は `argc` 個の `mnumber` を指している必要があります。
+unsafe extern "C" fn num_trampoline(
+    name: *mut c_char,
+    argc: c_int,
+    argv: *mut mnumber,
+    id: c_int,
+) -> mnumber {
+    let args = unsafe { num_args(argc, argv) };
+    math_result(eval_num(math_handler(id), &args), |message| {
+        report_error(name, message)
+    })
+}
+
+/// Zshから直接呼ばれる、文字列の引数を取る数式関数のブリッジ関数。
+///
+/// # Safety
+/// `arg` は有効なC文字列を指している必要があります。
+unsafe extern "C" fn str_trampoline(name: *mut c_char, arg: *mut c_char, id: c_int) -> mnumber {
+    let arg = if arg.is_null() {
+        String::new()
+    } else {
+        String::from_utf8_lossy(&unsafe { unmetafy_bytes(arg) }).into_owned()
+    };
+    math_result(eval_str(math_handler(id), &arg), |message| {
+        report_error(name, message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sum(args: &[ZshNumber]) -> ZshNumber {
+        if args.iter().all(|n| matches!(n, ZshNumber::Int(_))) {
+            ZshNumber::Int(args.iter().map(|n| n.as_f64() as bindings::zlong).sum())
+        } else {
+            ZshNumber::Float(args.iter().map(|n| n.as_f64()).sum())
+        }
+    }
+
+    fn div(args: &[ZshNumber]) -> MathResult {
+        match (args[0], args[1]) {
+            (_, ZshNumber::Int(0)) => Err("division by zero".into()),
+            (ZshNumber::Int(a), ZshNumber::Int(b)) => Ok(ZshNumber::Int(a / b)),
+            (a, b) => Ok(ZshNumber::Float(a.as_f64() / b.as_f64())),
+        }
+    }
+
+    fn length(arg: &str) -> ZshNumber {
+        ZshNumber::Int(arg.chars().count() as bindings::zlong)
+    }
+
+    fn parse(arg: &str) -> MathResult {
+        if let Ok(n) = arg.trim().parse() {
+            return Ok(ZshNumber::Int(n));
+        }
+        Ok(ZshNumber::Float(arg.trim().parse()?))
+    }
+
+    #[test]
+    fn raw_arguments_keep_int_and_float() {
+        let raw = [
+            ZshNumber::Int(3).into_raw(),
+            ZshNumber::Float(0.5).into_raw(),
+            ZshNumber::Int(-1).into_raw(),
+        ];
+        let args = unsafe { num_args(raw.len() as c_int, raw.as_ptr()) };
+        assert_eq!(
+            args,
+            [ZshNumber::Int(3), ZshNumber::Float(0.5), ZshNumber::Int(-1)]
+        );
+        assert!(unsafe { num_args(0, std::ptr::null()) }.is_empty());
+    }
+
+    #[test]
+    fn numeric_handler_result_is_converted_back() {
+        let kind = Some(MathKind::Num(sum));
+        let ints = [ZshNumber::Int(1), ZshNumber::Int(2)];
+        let mixed = [ZshNumber::Int(1), ZshNumber::Float(0.5)];
+        assert_eq!(eval_num(kind, &ints), Ok(ZshNumber::Int(3)));
+        assert_eq!(eval_num(kind, &mixed), Ok(ZshNumber::Float(1.5)));
+
+        let raw = math_result(eval_num(kind, &mixed), |_| panic!("no error expected"));
+        assert_eq!(raw.type_, bindings::MN_FLOAT as i32);
+        assert_eq!(ZshNumber::from_raw(raw), ZshNumber::Float(1.5));
+    }
+
+    #[test]
+    fn fallible_numeric_error_is_reported_and_yields_zero() {
+        let kind = Some(MathKind::NumFallible(div));
+        let args = [ZshNumber::Int(7), ZshNumber::Int(2)];
+        assert_eq!(eval_num(kind, &args), Ok(ZshNumber::Int(3)));
+
+        let args = [ZshNumber::Int(1), ZshNumber::Int(0)];
+        let mut reported = None;
+        let raw = math_result(eval_num(kind, &args), |m| reported = Some(m.to_string()));
+        assert_eq!(reported.as_deref(), Some("division by zero"));
+        assert_eq!(ZshNumber::from_raw(raw), ZshNumber::Int(0));
+    }
+
+    #[test]
+    fn string_handler_receives_argument_unevaluated() {
+        let kind = Some(MathKind::Str(length));
+        assert_eq!(eval_str(kind, "1 + 2"), Ok(ZshNumber::Int(5)));
+        assert_eq!(eval_str(kind, "日本"), Ok(ZshNumber::Int(2)));
+        assert_eq!(eval_str(kind, ""), Ok(ZshNumber::Int(0)));
+    }
+
+    #[test]
+    fn fallible_string_handler_result_mapping() {
+        let kind = Some(MathKind::StrFallible(parse));
+        assert_eq!(eval_str(kind, " 42 "), Ok(ZshNumber::Int(42)));
+        assert_eq!(eval_str(kind, "2.5"), Ok(ZshNumber::Float(2.5)));
+
+        let mut reported = None;
+        let raw = math_result(eval_str(kind, "abc"), |m| reported = Some(m.to_string()));
+        assert_eq!(reported.as_deref(), Some("invalid float literal"));
+        assert_eq!(raw.type_, bindings::MN_INTEGER as i32);
+        assert_eq!(ZshNumber::from_raw(raw), ZshNumber::Int(0));
+    }
+
+    #[test]
+    fn missing_handler_yields_zero() {
+        assert_eq!(eval_num(None, &[ZshNumber::Int(1)]), Ok(ZshNumber::Int(0)));
+        assert_eq!(eval_str(None, "1"), Ok(ZshNumber::Int(0)));
+        // 種類の異なるハンドラは呼び出さない
+        assert_eq!(
+            eval_str(Some(MathKind::Num(sum)), "1"),
+            Ok(ZshNumber::Int(0))
+        );
+    }
+}
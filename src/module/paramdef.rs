@@ -2,8 +2,469 @@
 //!
 //! Zshのモジュールはカスタムのパラメータを定義でき、これによりZshシェル内で
 //! 新しい変数（スカラ、配列、連想配列）を導入し、その振る舞いを制御することができます。
+//!
+//! [`Paramdef::scalar`] などのビルダーを使うと、値の取得・設定・削除のたびに
+//! Rustのクロージャが呼び出される特殊パラメータを定義できます。
 use crate::ZString;
-use crate::bindings::paramdef;
+use crate::bindings::{self, paramdef};
+use crate::envs::{heap_metafy, unmetafy_bytes};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{Arc, Mutex};
+
+/// 特殊パラメータの値を計算するゲッター。
+pub type ParamGetter<T> = Box<dyn Fn() -> T + Send + Sync>;
+/// 特殊パラメータへの代入時に呼び出されるセッター。
+pub type ParamSetter<T> = Box<dyn Fn(T) + Send + Sync>;
+/// 特殊パラメータの `unset` 時に呼び出されるハンドラ。
+pub type ParamUnsetter = Box<dyn Fn() + Send + Sync>;
+
+/// Rustのクロージャで値を提供する特殊パラメータのビルダー。
+///
+/// [`Paramdef::scalar`] で作成し、`getter` / `setter` / `unsetter` でハンドラを設定します。
+/// `Features::add_param` に渡すと、パラメータへのアクセスが登録したクロージャに委譲されます。
+///
+/// ```ignore
+/// Features::new().add_param(
+///     Paramdef::scalar("MYVAR")
+///         .getter(|| "hello".to_string())
+///         .setter(|v| eprintln!("MYVAR = {}", v)),
+/// )
+/// ```
+pub struct SpecialParam<T> {
+    name: String,
+    get: Option<ParamGetter<T>>,
+    set: Option<ParamSetter<T>>,
+    unset: Option<ParamUnsetter>,
+}
+
+impl<T> SpecialParam<T> {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            get: None,
+            set: None,
+            unset: None,
+        }
+    }
+
+    /// パラメータが参照されるたびに呼び出されるゲッターを設定します。
+    ///
+    /// ゲッターが設定されていない場合、パラメータは空の値を返します。
+    pub fn getter(mut self, f: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        self.get = Some(Box::new(f));
+        self
+    }
+
+    /// パラメータへの代入時に呼び出されるセッターを設定します。
+    ///
+    /// セッターが設定されていない場合、パラメータは読み取り専用 (`PM_READONLY`) になります。
+    pub fn setter(mut self, f: impl Fn(T) + Send + Sync + 'static) -> Self {
+        self.set = Some(Box::new(f));
+        self
+    }
+
+    /// パラメータが `unset` されたときに呼び出されるハンドラを設定します。
+    ///
+    /// ハンドラが設定されていない場合、`unset` は無視され、パラメータは値を提供し続けます。
+    pub fn unsetter(mut self, f: impl Fn() + Send + Sync + 'static) -> Self {
+        self.unset = Some(Box::new(f));
+        self
+    }
+
+    /// 登録用の `paramdef` フラグを計算します。
+    ///
+    /// Zshの `SPECIALPMDEF` と同様に `PM_SPECIAL | PM_HIDE | PM_HIDEVAL` を付与します。
+    fn flags(&self, type_flag: u32) -> i32 {
+        let mut flags = type_flag | bindings::PM_SPECIAL | bindings::PM_HIDE | bindings::PM_HIDEVAL;
+        if self.set.is_none() {
+            flags |= bindings::PM_READONLY;
+        }
+        flags as i32
+    }
+
+    fn get_value(&self) -> Option<T> {
+        self.get.as_ref().map(|get| get())
+    }
+
+    fn set_value(&self, value: T) {
+        if let Some(set) = &self.set {
+            set(value);
+        }
+    }
+
+    fn unset_value(&self) -> bool {
+        match &self.unset {
+            Some(unset) => {
+                unset();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 特殊連想配列の要素を1つ検索するハンドラ。キーが存在しない場合は `None` を返します。
+pub type HashLookup = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+/// 特殊連想配列の全てのキーを列挙するハンドラ。
+pub type HashKeys = Box<dyn Fn() -> Vec<String> + Send + Sync>;
+
+/// Rustのクロージャで要素を提供する特殊連想配列 (`$commands` のようなもの) のビルダー。
+///
+/// [`Paramdef::hash`] で作成します。`${name[key]}` は `lookup` をそのキーについてのみ呼び出すため、
+/// 全ての要素を事前に計算する必要はありません。`${(k)name}` や `${(kv)name}` のような
+/// 全体の走査では、`keys` で列挙したキーごとに `lookup` が呼び出されます。
+///
+/// ```ignore
+/// Features::new().add_param(
+///     Paramdef::hash("git_status")
+///         .lookup(|key| match key {
+///             "branch" => Some(current_branch()),
+///             _ => None,
+///         })
+///         .keys(|| vec!["branch".to_string()]),
+/// )
+/// ```
+pub struct HashParam {
+    name: String,
+    lookup: Option<HashLookup>,
+    keys: Option<HashKeys>,
+}
+
+impl HashParam {
+    /// `${name[key]}` が参照されたときに呼び出される検索ハンドラを設定します。
+    pub fn lookup(mut self, f: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        self.lookup = Some(Box::new(f));
+        self
+    }
+
+    /// 連想配列全体を走査するときに呼び出される、キーの列挙ハンドラを設定します。
+    ///
+    /// 設定されていない場合、連想配列は空として走査されます。
+    pub fn keys(mut self, f: impl Fn() -> Vec<String> + Send + Sync + 'static) -> Self {
+        self.keys = Some(Box::new(f));
+        self
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.lookup.as_ref().and_then(|lookup| lookup(key))
+    }
+
+    fn all_keys(&self) -> Vec<String> {
+        self.keys.as_ref().map(|keys| keys()).unwrap_or_default()
+    }
+}
+
+/// 登録された特殊パラメータのハンドラ。型ごとに対応するGSUトランポリンから参照されます。
+pub(crate) enum ParamHandlers {
+    Scalar(SpecialParam<String>),
+    Integer(SpecialParam<bindings::zlong>),
+    Float(SpecialParam<f64>),
+    Array(SpecialParam<Vec<String>>),
+    Hash(HashParam),
+}
+
+/// 登録された特殊パラメータのハンドラをグローバルに管理するためのミューテックス保護されたベクタ。
+/// (パラメータ名, ハンドラ) のタプルを格納します。
+///
+/// ハンドラ内から他のパラメータにアクセスしても再入でデッドロックしないよう、
+/// トランポリンは `Arc` を複製してからロックを解放し、ハンドラを呼び出します。
+static PARAM_HANDLERS: Mutex<Vec<(String, Arc<ParamHandlers>)>> = Mutex::new(Vec::new());
+
+/// 特殊パラメータのハンドラをグローバルディスパッチャに登録します。
+///
+/// 同じ名前のハンドラが既に登録されている場合は置き換えられます。
+pub(crate) fn register_param(name: &str, handlers: Arc<ParamHandlers>) {
+    if let Ok(mut list) = PARAM_HANDLERS.lock() {
+        list.retain(|(n, _)| n != name);
+        list.push((name.to_string(), handlers));
+    }
+}
+
+/// Zshのパラメータノードから、その名前で登録されたハンドラを検索します。
+fn handlers_for(pm: bindings::Param) -> Option<Arc<ParamHandlers>> {
+    if pm.is_null() {
+        return None;
+    }
+    let name = unsafe {
+        let nam = (*pm).node.nam;
+        if nam.is_null() {
+            return None;
+        }
+        CStr::from_ptr(nam)
+    };
+    let name = name.to_str().ok()?;
+    let list = PARAM_HANDLERS.lock().ok()?;
+    list.iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, h)| Arc::clone(h))
+}
+
+/// スカラ特殊パラメータのゲッター・トランポリン。
+///
+/// 戻り値はZshのヒープ上に確保されるため、Zsh側で解放する必要はありません。
+unsafe extern "C" fn scalar_getfn(pm: bindings::Param) -> *mut c_char {
+    let value = match handlers_for(pm).as_deref() {
+        Some(ParamHandlers::Scalar(p)) => p.get_value().unwrap_or_default(),
+        _ => String::new(),
+    };
+    heap_metafy(value.as_bytes())
+}
+
+/// スカラ特殊パラメータのセッター・トランポリン。
+///
+/// Zshは `ztrdup` された値の所有権を渡してくるため、ここで解放します。
+/// `stdunsetfn` などからは `NULL` が渡されることがあり、その場合は何もしません。
+unsafe extern "C" fn scalar_setfn(pm: bindings::Param, value: *mut c_char) {
+    if value.is_null() {
+        return;
+    }
+    let bytes = unsafe { unmetafy_bytes(value) };
+    unsafe { bindings::zsfree(value) };
+    if let Some(ParamHandlers::Scalar(p)) = handlers_for(pm).as_deref() {
+        p.set_value(String::from_utf8_lossy(&bytes).into_owned());
+    }
+}
+
+/// 整数特殊パラメータのゲッター・トランポリン。
+unsafe extern "C" fn integer_getfn(pm: bindings::Param) -> bindings::zlong {
+    match handlers_for(pm).as_deref() {
+        Some(ParamHandlers::Integer(p)) => p.get_value().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+/// 整数特殊パラメータのセッター・トランポリン。
+unsafe extern "C" fn integer_setfn(pm: bindings::Param, value: bindings::zlong) {
+    if let Some(ParamHandlers::Integer(p)) = handlers_for(pm).as_deref() {
+        p.set_value(value);
+    }
+}
+
+/// 浮動小数点特殊パラメータのゲッター・トランポリン。
+unsafe extern "C" fn float_getfn(pm: bindings::Param) -> f64 {
+    match handlers_for(pm).as_deref() {
+        Some(ParamHandlers::Float(p)) => p.get_value().unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+/// 浮動小数点特殊パラメータのセッター・トランポリン。
+unsafe extern "C" fn float_setfn(pm: bindings::Param, value: f64) {
+    if let Some(ParamHandlers::Float(p)) = handlers_for(pm).as_deref() {
+        p.set_value(value);
+    }
+}
+
+/// 配列特殊パラメータのゲッター・トランポリン。
+///
+/// 配列と各要素はZshのヒープ上に確保されるため、Zsh側で解放する必要はありません。
+unsafe extern "C" fn array_getfn(pm: bindings::Param) -> *mut *mut c_char {
+    let values = match handlers_for(pm).as_deref() {
+        Some(ParamHandlers::Array(p)) => p.get_value().unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    unsafe {
+        let size = (values.len() + 1) * std::mem::size_of::<*mut c_char>();
+        let array = bindings::zhalloc(size) as *mut *mut c_char;
+        for (i, v) in values.iter().enumerate() {
+            *array.add(i) = heap_metafy(v.as_bytes());
+        }
+        *array.add(values.len()) = std::ptr::null_mut();
+        array
+    }
+}
+
+/// 配列特殊パラメータのセッター・トランポリン。
+///
+/// Zshは `zalloc` された配列の所有権を渡してくるため、ここで解放します。
+unsafe extern "C" fn array_setfn(pm: bindings::Param, value: *mut *mut c_char) {
+    if value.is_null() {
+        return;
+    }
+    let mut values = Vec::new();
+    unsafe {
+        let mut curr = value;
+        while !(*curr).is_null() {
+            values.push(String::from_utf8_lossy(&unmetafy_bytes(*curr)).into_owned());
+            curr = curr.add(1);
+        }
+        bindings::freearray(value);
+    }
+    if let Some(ParamHandlers::Array(p)) = handlers_for(pm).as_deref() {
+        p.set_value(values);
+    }
+}
+
+/// 特殊パラメータ共通のアンセッター・トランポリン。
+///
+/// Rust側のハンドラが呼び出された場合のみ、`stdunsetfn` と同様に `PM_UNSET` を立てます。
+unsafe extern "C" fn param_unsetfn(pm: bindings::Param, _exp: c_int) {
+    let unset = match handlers_for(pm).as_deref() {
+        Some(ParamHandlers::Scalar(p)) => p.unset_value(),
+        Some(ParamHandlers::Integer(p)) => p.unset_value(),
+        Some(ParamHandlers::Float(p)) => p.unset_value(),
+        Some(ParamHandlers::Array(p)) => p.unset_value(),
+        Some(ParamHandlers::Hash(_)) | None => false,
+    };
+    if unset {
+        unsafe { (*pm).node.flags |= bindings::PM_UNSET as i32 };
+    }
+}
+
+/// 特殊連想配列のハッシュテーブルから、それを所有するパラメータのハンドラを検索します。
+///
+/// `createspecialhash` が作成するテーブルには名前が記録されないため、登録済みの
+/// 連想配列パラメータを `realparamtab` から引き、`u.hash` が一致するものを探します。
+/// `local` で隠されている場合に備え、`old` のチェーンも辿ります。
+fn hash_handlers_for(ht: bindings::HashTable) -> Option<Arc<ParamHandlers>> {
+    if ht.is_null() {
+        return None;
+    }
+    let candidates: Vec<(String, Arc<ParamHandlers>)> = PARAM_HANDLERS
+        .lock()
+        .ok()?
+        .iter()
+        .filter(|(_, h)| matches!(**h, ParamHandlers::Hash(_)))
+        .map(|(n, h)| (n.clone(), Arc::clone(h)))
+        .collect();
+
+    unsafe {
+        let table = bindings::realparamtab;
+        let getnode = (*table).getnode2?;
+        for (name, handlers) in candidates {
+            let Ok(c_name) = CString::new(name) else {
+                continue;
+            };
+            let mut pm = getnode(table, c_name.as_ptr()) as bindings::Param;
+            while !pm.is_null() {
+                if (*pm).node.flags & bindings::PM_HASHED as i32 != 0 && (*pm).u.hash == ht {
+                    return Some(handlers);
+                }
+                pm = (*pm).old;
+            }
+        }
+    }
+    None
+}
+
+/// 特殊連想配列の要素パラメータを初期化します。
+///
+/// 要素は読み取り専用のスカラで、値は `u.str` に保持されます。
+unsafe fn init_hash_elem(pm: bindings::Param, name: *mut c_char, value: Option<&str>) {
+    unsafe {
+        (*pm).node.nam = name;
+        (*pm).node.flags = (bindings::PM_SCALAR | bindings::PM_READONLY) as i32;
+        (*pm).gsu.s = &HASH_ELEM_GSU as *const bindings::gsu_scalar as bindings::GsuScalar;
+        match value {
+            Some(v) => (*pm).u.str_ = heap_metafy(v.as_bytes()),
+            None => {
+                // zsh/parameter と同様に、存在しないキーは空の `PM_UNSET` 要素として返します。
+                (*pm).u.str_ = heap_metafy(b"");
+                (*pm).node.flags |= (bindings::PM_UNSET | bindings::PM_SPECIAL) as i32;
+            }
+        }
+    }
+}
+
+/// 特殊連想配列の `getnode` トランポリン。`${name[key]}` の参照時に呼び出されます。
+///
+/// 要素はZshのヒープ上に確保されるため、Zsh側で解放する必要はありません。
+unsafe extern "C" fn hash_getnode(
+    ht: bindings::HashTable,
+    key: *const c_char,
+) -> bindings::HashNode {
+    let key_str = unsafe { String::from_utf8_lossy(&unmetafy_bytes(key)).into_owned() };
+    let value = match hash_handlers_for(ht).as_deref() {
+        Some(ParamHandlers::Hash(p)) => p.get(&key_str),
+        _ => None,
+    };
+    unsafe {
+        let pm = bindings::hcalloc(std::mem::size_of::<bindings::param>()) as bindings::Param;
+        init_hash_elem(pm, bindings::dupstring(key), value.as_deref());
+        &mut (*pm).node
+    }
+}
+
+/// 特殊連想配列の `scantab` トランポリン。連想配列全体の走査時に呼び出されます。
+///
+/// zsh/parameter の実装と同様に、値が必要な走査の場合のみ各キーについて `lookup` を呼び出します。
+unsafe extern "C" fn hash_scantab(ht: bindings::HashTable, func: bindings::ScanFunc, flags: c_int) {
+    let Some(func) = func else {
+        return;
+    };
+    let handlers = hash_handlers_for(ht);
+    let Some(ParamHandlers::Hash(p)) = handlers.as_deref() else {
+        return;
+    };
+    let flags_u = flags as u32;
+    let want_vals = flags_u & (bindings::SCANPM_WANTVALS | bindings::SCANPM_MATCHVAL) != 0
+        || flags_u & bindings::SCANPM_WANTKEYS == 0;
+
+    for key in p.all_keys() {
+        let value = if want_vals { p.get(&key) } else { None };
+        unsafe {
+            let mut pm: bindings::param = std::mem::zeroed();
+            init_hash_elem(
+                &mut pm,
+                heap_metafy(key.as_bytes()),
+                Some(value.as_deref().unwrap_or_default()),
+            );
+            func(&mut pm.node, flags);
+        }
+    }
+}
+
+/// 特殊連想配列の要素のゲッター。`u.str` に保持された値を返します。
+unsafe extern "C" fn hash_elem_getfn(pm: bindings::Param) -> *mut c_char {
+    unsafe { (*pm).u.str_ }
+}
+
+/// 特殊連想配列の要素のセッター。要素は読み取り専用のため、値を解放するだけです。
+unsafe extern "C" fn hash_elem_setfn(_pm: bindings::Param, value: *mut c_char) {
+    if !value.is_null() {
+        unsafe { bindings::zsfree(value) };
+    }
+}
+
+/// 特殊連想配列の要素のアンセッター。要素は都度生成されるため、何もしません。
+unsafe extern "C" fn hash_elem_unsetfn(_pm: bindings::Param, _exp: c_int) {}
+
+/// 特殊連想配列の要素で共有されるGSUテーブル。
+static HASH_ELEM_GSU: bindings::gsu_scalar = bindings::gsu_scalar {
+    getfn: Some(hash_elem_getfn),
+    setfn: Some(hash_elem_setfn),
+    unsetfn: Some(hash_elem_unsetfn),
+};
+
+/// 全てのスカラ特殊パラメータで共有されるGSUテーブル。
+/// ハンドラはパラメータ名で `PARAM_HANDLERS` から検索されます。
+static SCALAR_GSU: bindings::gsu_scalar = bindings::gsu_scalar {
+    getfn: Some(scalar_getfn),
+    setfn: Some(scalar_setfn),
+    unsetfn: Some(param_unsetfn),
+};
+
+/// 全ての整数特殊パラメータで共有されるGSUテーブル。
+static INTEGER_GSU: bindings::gsu_integer = bindings::gsu_integer {
+    getfn: Some(integer_getfn),
+    setfn: Some(integer_setfn),
+    unsetfn: Some(param_unsetfn),
+};
+
+/// 全ての浮動小数点特殊パラメータで共有されるGSUテーブル。
+static FLOAT_GSU: bindings::gsu_float = bindings::gsu_float {
+    getfn: Some(float_getfn),
+    setfn: Some(float_setfn),
+    unsetfn: Some(param_unsetfn),
+};
+
+/// 全ての配列特殊パラメータで共有されるGSUテーブル。
+static ARRAY_GSU: bindings::gsu_array = bindings::gsu_array {
+    getfn: Some(array_getfn),
+    setfn: Some(array_setfn),
+    unsetfn: Some(param_unsetfn),
+};
 
 /// Zshのパラメータ定義をカプセル化する構造体。
 ///
@@ -14,9 +475,75 @@ pub struct Paramdef {
     flags: i32,
     var: *mut std::os::raw::c_void, // 外部変数のポインタ (例: i8, i32, char*, char**など)
     gsu: *const std::os::raw::c_void, // Get/Set/Unsetハンドラへのポインタ
+    getnfn: bindings::GetNodeFunc,  // 特殊連想配列の要素検索関数
+    scantfn: bindings::ScanTabFunc, // 特殊連想配列の走査関数
+    handlers: Option<(String, Arc<ParamHandlers>)>, // Rust側のハンドラ (ビルダーで作成した場合)
 }
 
 impl Paramdef {
+    /// Rustのクロージャで値を提供するスカラ特殊パラメータのビルダーを作成します。
+    ///
+    /// `$name` を参照するとゲッターが、代入するとセッターが呼び出されます。
+    pub fn scalar(name: &str) -> SpecialParam<String> {
+        SpecialParam::new(name)
+    }
+
+    /// Rustのクロージャで値を提供する整数特殊パラメータ (`typeset -i` 相当) のビルダーを作成します。
+    pub fn integer(name: &str) -> SpecialParam<bindings::zlong> {
+        SpecialParam::new(name)
+    }
+
+    /// Rustのクロージャで値を提供する浮動小数点特殊パラメータ (`typeset -F` 相当) のビルダーを作成します。
+    pub fn float(name: &str) -> SpecialParam<f64> {
+        SpecialParam::new(name)
+    }
+
+    /// Rustのクロージャで値を提供する配列特殊パラメータのビルダーを作成します。
+    ///
+    /// `$name` や `${name[i]}` を参照するたびにゲッターが呼び出され、配列全体が再計算されます。
+    pub fn array(name: &str) -> SpecialParam<Vec<String>> {
+        SpecialParam::new(name)
+    }
+
+    /// Rustのクロージャで要素を提供する特殊連想配列のビルダーを作成します。
+    ///
+    /// 作成されるパラメータは読み取り専用で、要素は参照のたびに計算されます。
+    pub fn hash(name: &str) -> HashParam {
+        HashParam {
+            name: name.to_string(),
+            lookup: None,
+            keys: None,
+        }
+    }
+
+    /// ビルダーと型ごとのGSUテーブルから `Paramdef` を作成します。
+    fn special<T>(
+        param: SpecialParam<T>,
+        type_flag: u32,
+        gsu: *const c_void,
+        wrap: fn(SpecialParam<T>) -> ParamHandlers,
+    ) -> Self {
+        let name = param.name.clone();
+        Paramdef {
+            name: ZString::new(&name),
+            flags: param.flags(type_flag),
+            var: std::ptr::null_mut(),
+            gsu,
+            getnfn: None,
+            scantfn: None,
+            handlers: Some((name, Arc::new(wrap(param)))),
+        }
+    }
+
+    /// `Paramdef` のRust側ハンドラを取り出します。
+    ///
+    /// `Features::add_param` が、ハンドラをグローバルディスパッチャに登録するために使用します。
+    pub(crate) fn handlers(&self) -> Option<(&str, Arc<ParamHandlers>)> {
+        self.handlers
+            .as_ref()
+            .map(|(name, h)| (name.as_str(), Arc::clone(h)))
+    }
+
     /// `Paramdef`インスタンスをZshの`paramdef`構造体として表現します。
     ///
     /// この生構造体はZshのモジュールAPIに渡され、パラメータとして登録されます。
@@ -30,7 +557,55 @@ impl Paramdef {
             flags: self.flags,
             var: self.var,
             gsu: self.gsu,
+            getnfn: self.getnfn,
+            scantfn: self.scantfn,
             ..unsafe { std::mem::zeroed() }
         }
     }
 }
+
+impl From<SpecialParam<String>> for Paramdef {
+    fn from(param: SpecialParam<String>) -> Self {
+        let gsu = &SCALAR_GSU as *const bindings::gsu_scalar as *const c_void;
+        Paramdef::special(param, bindings::PM_SCALAR, gsu, ParamHandlers::Scalar)
+    }
+}
+
+impl From<SpecialParam<bindings::zlong>> for Paramdef {
+    fn from(param: SpecialParam<bindings::zlong>) -> Self {
+        let gsu = &INTEGER_GSU as *const bindings::gsu_integer as *const c_void;
+        Paramdef::special(param, bindings::PM_INTEGER, gsu, ParamHandlers::Integer)
+    }
+}
+
+impl From<SpecialParam<f64>> for Paramdef {
+    fn from(param: SpecialParam<f64>) -> Self {
+        let gsu = &FLOAT_GSU as *const bindings::gsu_float as *const c_void;
+        Paramdef::special(param, bindings::PM_FFLOAT, gsu, ParamHandlers::Float)
+    }
+}
+
+impl From<SpecialParam<Vec<String>>> for Paramdef {
+    fn from(param: SpecialParam<Vec<String>>) -> Self {
+        let gsu = &ARRAY_GSU as *const bindings::gsu_array as *const c_void;
+        Paramdef::special(param, bindings::PM_ARRAY, gsu, ParamHandlers::Array)
+    }
+}
+
+impl From<HashParam> for Paramdef {
+    fn from(param: HashParam) -> Self {
+        let name = param.name.clone();
+        // `getnfn` が設定されている場合、Zsh は `createspecialhash` で `PM_HASHED` を付与します。
+        let flags =
+            bindings::PM_READONLY | bindings::PM_SPECIAL | bindings::PM_HIDE | bindings::PM_HIDEVAL;
+        Paramdef {
+            name: ZString::new(&name),
+            flags: flags as i32,
+            var: std::ptr::null_mut(),
+            gsu: std::ptr::null(),
+            getnfn: Some(hash_getnode),
+            scantfn: Some(hash_scantab),
+            handlers: Some((name, Arc::new(ParamHandlers::Hash(param)))),
+        }
+    }
+}
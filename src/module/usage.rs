@@ -0,0 +1,260 @@
+//! このモジュールは、ビルトインコマンドの使い方 (usage) とヘルプの表示を提供します。
+//!
+//! [`BuiltinSpec`](crate::BuiltinSpec) に説明やオプションのヘルプを設定すると、
+//! `name --help` (オプション文字列を宣言している場合は `name -h`) でヘルプが表示され、
+//! 引数の数が範囲外の場合はエラーと共に使い方が表示されます。
+//! オプション文字列で `h` を宣言したビルトインでは、ヘルプは無効になります。
+use crate::output::{ZshErr, ZshOut, error_named};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// ビルトインのヘルプの表示に必要な情報。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BuiltinHelp {
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    /// コマンド名とオプションに続く引数部分の書式 (例: `"count dest [label]"`)。
+    pub(crate) synopsis: Option<String>,
+    pub(crate) optstr: Option<String>,
+    pub(crate) option_help: Vec<(char, String)>,
+    pub(crate) min_args: i32,
+    pub(crate) max_args: i32,
+}
+
+impl BuiltinHelp {
+    /// ヘルプの表示に `-h` オプションを使用するかどうかを返します。
+    ///
+    /// オプション文字列を宣言したビルトインでは、Zshが `--help` を不正なオプションとして扱うため、
+    /// 代わりに `-h` を使用します。オプション文字列で `h` を宣言したビルトインは
+    /// ヘルプを持たないため (`BuiltinSpec::help`)、`-h` と衝突することはありません。
+    pub(crate) fn uses_h_option(&self) -> bool {
+        self.optstr.is_some()
+    }
+
+    /// `name [-lv] [-x arg] count dest [label]` の形式の使い方を返します。
+    pub(crate) fn usage_line(&self) -> String {
+        let mut parts = vec![self.name.clone()];
+        if let Some(optstr) = &self.optstr {
+            let flags: String = parse_optstr(optstr)
+                .filter(|(_, takes_arg)| !takes_arg)
+                .map(|(c, _)| c)
+                .collect();
+            if !flags.is_empty() {
+                parts.push(format!("[-{}]", flags));
+            }
+            parts.extend(
+                parse_optstr(optstr)
+                    .filter(|(_, takes_arg)| *takes_arg)
+                    .map(|(c, _)| format!("[-{} arg]", c)),
+            );
+        }
+        match &self.synopsis {
+            Some(synopsis) => parts.push(synopsis.clone()),
+            None if self.max_args != 0 => parts.push("[arg ...]".to_string()),
+            None => {}
+        }
+        parts.join(" ")
+    }
+
+    /// 使い方、説明、オプションの一覧からなるヘルプを返します。
+    pub(crate) fn help_text(&self) -> String {
+        let mut text = format!("usage: {}\n", self.usage_line());
+        if let Some(description) = &self.description {
+            text.push('\n');
+            text.push_str(description);
+            text.push('\n');
+        }
+        if !self.option_help.is_empty() {
+            text.push_str("\noptions:\n");
+            for (c, help) in &self.option_help {
+                let takes_arg = parse_optstr(self.optstr.as_deref().unwrap_or(""))
+                    .any(|(o, takes_arg)| o == *c && takes_arg);
+                let flag = if takes_arg {
+                    format!("-{} arg", c)
+                } else {
+                    format!("-{}", c)
+                };
+                text.push_str(&format!("  {:<10} {}\n", flag, help));
+            }
+        }
+        text
+    }
+
+    /// 引数の数を検査し、範囲外の場合はZshと同じ文言のエラーメッセージを返します。
+    pub(crate) fn check_count(&self, count: usize) -> Result<(), &'static str> {
+        if (count as i64) < self.min_args as i64 {
+            Err("not enough arguments")
+        } else if self.max_args >= 0 && count as i64 > self.max_args as i64 {
+            Err("too many arguments")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// オプション文字列を (オプション文字, 引数を取るか) の列に分解します。
+pub(crate) fn parse_optstr(optstr: &str) -> impl Iterator<Item = (char, bool)> + '_ {
+    let chars: Vec<char> = optstr.chars().collect();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        if c == ':' || c == '%' {
+            continue;
+        }
+        result.push((c, chars.get(i) == Some(&':')));
+    }
+    result.into_iter()
+}
+
+/// 登録されたビルトインのヘルプ。(コマンド名, `BuiltinHelp`) のタプルを格納します。
+static HELPS: Mutex<Vec<(String, Arc<BuiltinHelp>)>> = Mutex::new(Vec::new());
+
+/// ビルトインのヘルプを登録します。
+pub(crate) fn register_help(help: BuiltinHelp) {
+    if let Ok(mut h) = HELPS.lock()
+        && !h.iter().any(|(n, _)| *n == help.name)
+    {
+        h.push((help.name.clone(), Arc::new(help)));
+    }
+}
+
+/// 指定された名前のビルトインのヘルプを返します。
+pub(crate) fn help_for(name: &str) -> Option<Arc<BuiltinHelp>> {
+    HELPS
+        .lock()
+        .ok()?
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, h)| h.clone())
+}
+
+/// 指定された名前のビルトインの使い方を `usage: ...` の形式で標準エラー出力に表示します。
+pub(crate) fn print_usage(name: &str) {
+    if let Some(help) = help_for(name) {
+        let _ = writeln!(ZshErr::new(), "usage: {}", help.usage_line());
+    }
+}
+
+/// ヘルプの要求と引数の数を処理します。
+///
+/// ヘルプを表示した場合や引数の数が範囲外の場合は、ハンドラを呼ばずに返す終了ステータスを返します。
+pub(crate) fn handle_help(
+    help: &BuiltinHelp,
+    args: &[Vec<u8>],
+    help_requested: bool,
+) -> Option<i32> {
+    let help_requested = if help.uses_h_option() {
+        help_requested
+    } else {
+        help.optstr.is_none() && args.first().is_some_and(|a| a == b"--help")
+    };
+    if help_requested {
+        let mut out = ZshOut::new();
+        let _ = out.write_all(help.help_text().as_bytes());
+        return Some(0);
+    }
+    if let Err(message) = help.check_count(args.len()) {
+        error_named(&help.name, message);
+        print_usage(&help.name);
+        return Some(1);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn greet() -> BuiltinHelp {
+        BuiltinHelp {
+            name: "greet".to_string(),
+            description: Some("Print a greeting.".to_string()),
+            synopsis: Some("name [greeting ...]".to_string()),
+            optstr: Some("lv:".to_string()),
+            option_help: vec![
+                ('l', "use the long form".to_string()),
+                ('v', "set the verbosity level".to_string()),
+            ],
+            min_args: 1,
+            max_args: 3,
+        }
+    }
+
+    #[test]
+    fn parse_optstr_marks_options_taking_arguments() {
+        let parsed: Vec<(char, bool)> = parse_optstr("ab:c%d:").collect();
+        assert_eq!(
+            parsed,
+            vec![('a', false), ('b', true), ('c', false), ('d', true)]
+        );
+        assert_eq!(parse_optstr("").count(), 0);
+    }
+
+    #[test]
+    fn usage_line_lists_flags_then_options_with_arguments() {
+        assert_eq!(
+            greet().usage_line(),
+            "greet [-l] [-v arg] name [greeting ...]"
+        );
+    }
+
+    #[test]
+    fn usage_line_without_synopsis_depends_on_max_args() {
+        let mut help = BuiltinHelp {
+            name: "f".to_string(),
+            max_args: -1,
+            ..Default::default()
+        };
+        assert_eq!(help.usage_line(), "f [arg ...]");
+        help.max_args = 0;
+        assert_eq!(help.usage_line(), "f");
+        help.optstr = Some("x:".to_string());
+        assert_eq!(help.usage_line(), "f [-x arg]");
+    }
+
+    #[test]
+    fn help_text_contains_usage_description_and_options() {
+        assert_eq!(
+            greet().help_text(),
+            "usage: greet [-l] [-v arg] name [greeting ...]\n\
+             \n\
+             Print a greeting.\n\
+             \n\
+             options:\n  \
+             -l         use the long form\n  \
+             -v arg     set the verbosity level\n"
+        );
+    }
+
+    #[test]
+    fn help_text_without_description_is_only_usage() {
+        let help = BuiltinHelp {
+            name: "f".to_string(),
+            synopsis: Some("file".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(help.help_text(), "usage: f file\n");
+    }
+
+    #[test]
+    fn check_count_uses_inclusive_bounds() {
+        let help = greet();
+        assert_eq!(help.check_count(0), Err("not enough arguments"));
+        assert_eq!(help.check_count(1), Ok(()));
+        assert_eq!(help.check_count(3), Ok(()));
+        assert_eq!(help.check_count(4), Err("too many arguments"));
+    }
+
+    #[test]
+    fn check_count_without_maximum() {
+        let help = BuiltinHelp {
+            min_args: 0,
+            max_args: -1,
+            ..Default::default()
+        };
+        assert_eq!(help.check_count(0), Ok(()));
+        assert_eq!(help.check_count(1000), Ok(()));
+    }
+}
@@ -0,0 +1,177 @@
+//! このモジュールは、ビルトインコマンドからZshの標準出力・標準エラー出力へ書き込むための機能を提供します。
+//!
+//! `println!` はRust独自のバッファを経由して直接ファイルディスクリプタに書き込むため、
+//! Zshの `stdout` (Cの `FILE*`) にバッファされた出力と順序が入れ替わることがあります。
+//! [`ZshOut`] と [`ZshErr`] はZsh自身のビルトインと同じ `FILE*` に書き込むため、
+//! `$(...)` や `>file` などのリダイレクトの下でも正しく出力されます。
+//!
+//! エラーメッセージには [`warn!`](crate::warn) と [`error!`](crate::error) マクロを使用します。
+//! これらはZshの `zwarnnam` / `zerrnam` を呼び出し、`name: message` の形式で表示します。
+use crate::bindings;
+use crate::envs::heap_metafy;
+use std::ffi::c_void;
+use std::io;
+use std::os::raw::c_char;
+
+/// Cの `FILE*` に書き込みます。
+///
+/// # Safety
+/// `file` は有効な `FILE*` である必要があります。
+unsafe fn write_file(file: *mut bindings::FILE, buf: &[u8]) -> io::Result<usize> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    let written =
+        unsafe { bindings::fwrite(buf.as_ptr() as *const c_void, 1, buf.len() as _, file) };
+    if written == 0 {
+        // `fwrite` は書き込めなかった理由を errno に設定するとは限らないため、
+        // ストリームのエラー状態を確認し、理由が分からない場合は `WriteZero` を返す
+        let err = io::Error::last_os_error();
+        if unsafe { bindings::ferror(file) } != 0 && err.raw_os_error().is_some_and(|e| e != 0) {
+            return Err(err);
+        }
+        return Err(io::Error::from(io::ErrorKind::WriteZero));
+    }
+    Ok(written as usize)
+}
+
+/// Cの `FILE*` をフラッシュします。
+///
+/// # Safety
+/// `file` は有効な `FILE*` である必要があります。
+unsafe fn flush_file(file: *mut bindings::FILE) -> io::Result<()> {
+    if unsafe { bindings::fflush(file) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Zshの標準出力 (`stdout`) に書き込むライター。
+///
+/// Zshのビルトイン (`print` など) と同じ `FILE*` を使用するため、
+/// ビルトインに対するリダイレクトやコマンド置換がそのまま適用されます。
+/// 破棄時に自動的にフラッシュされます。
+///
+/// ```ignore
+/// use std::io::Write;
+///
+/// fn hello(_name: &str, args: &[&str]) -> i32 {
+///     let mut out = ZshOut::new();
+///     match writeln!(out, "hello, {}", args.join(" ")) {
+///         Ok(()) => 0,
+///         Err(_) => 1,
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ZshOut {
+    _private: (),
+}
+
+impl ZshOut {
+    /// 新しい `ZshOut` を作成します。
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl io::Write for ZshOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        unsafe { write_file(bindings::stdout, buf) }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        unsafe { flush_file(bindings::stdout) }
+    }
+}
+
+impl Drop for ZshOut {
+    fn drop(&mut self) {
+        let _ = io::Write::flush(self);
+    }
+}
+
+/// Zshの標準エラー出力 (`stderr`) に書き込むライター。
+///
+/// 書き込みはバッファされずにすぐ出力されます。
+#[derive(Debug, Default)]
+pub struct ZshErr {
+    _private: (),
+}
+
+impl ZshErr {
+    /// 新しい `ZshErr` を作成します。
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl io::Write for ZshErr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = unsafe { write_file(bindings::stderr, buf) }?;
+        unsafe { flush_file(bindings::stderr) }?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        unsafe { flush_file(bindings::stderr) }
+    }
+}
+
+/// `name: message` の形式で警告を表示します。
+///
+/// Zshの `zwarnnam` を使用するため、`$errflag` は変更されず、コマンドの実行は継続します。
+/// 通常は [`warn!`](crate::warn) マクロから使用します。
+pub fn warn_named(name: &str, message: &str) {
+    unsafe {
+        bindings::zwarnnam(
+            heap_metafy(name.as_bytes()),
+            c"%s".as_ptr() as *mut c_char,
+            heap_metafy(message.as_bytes()),
+        );
+    }
+}
+
+/// `name: message` の形式でエラーを表示し、Zshのエラーフラグを設定します。
+///
+/// Zshの `zerrnam` を使用するため、現在のコマンドリストの実行は中断されます。
+/// 通常は [`error!`](crate::error) マクロから使用します。
+pub fn error_named(name: &str, message: &str) {
+    unsafe {
+        bindings::zerrnam(
+            heap_metafy(name.as_bytes()),
+            c"%s".as_ptr() as *mut c_char,
+            heap_metafy(message.as_bytes()),
+        );
+    }
+}
+
+/// ビルトイン名を前置して警告を表示します。
+///
+/// 1つ目の引数にはビルトイン名 (`ctx.name()` やハンドラの `name` 引数) を指定し、
+/// 以降は `format!` と同じ形式でメッセージを指定します。
+///
+/// ```ignore
+/// zsh_system::warn!(name, "ignoring unknown key: {}", key);
+/// ```
+#[macro_export]
+macro_rules! warn {
+    ($name:expr, $($arg:tt)+) => {
+        $crate::warn_named($name, &::std::format!($($arg)+))
+    };
+}
+
+/// ビルトイン名を前置してエラーを表示し、Zshのエラーフラグを設定します。
+///
+/// 引数の形式は [`warn!`](crate::warn) と同じです。
+///
+/// ```ignore
+/// zsh_system::error!(ctx.name(), "no such file: {}", path.display());
+/// return 1;
+/// ```
+#[macro_export]
+macro_rules! error {
+    ($name:expr, $($arg:tt)+) => {
+        $crate::error_named($name, &::std::format!($($arg)+))
+    };
+}
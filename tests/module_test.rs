@@ -55,6 +55,20 @@ mod test_stubs {
         unsafe { libc::strdup(s) }
     }
 
+    // cleanup_ でのパラメータ監視の解除が参照するパラメータテーブル
+    #[unsafe(no_mangle)]
+    pub static mut paramtab: *mut c_void = std::ptr::null_mut();
+
+    // boot_ での補完関数の登録が参照するスクリプトの実行関数
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn execstring(
+        _s: *mut c_char,
+        _dont_change_job: i32,
+        _exiting: i32,
+        _context: *mut c_char,
+    ) {
+    }
+
     // zshの機能をエミュレートするための空関数
     #[unsafe(no_mangle)]
     pub unsafe extern "C" fn setfeatureenables(
@@ -0,0 +1,490 @@
+//! `zsh-system` のための手続き型マクロを提供するクレートです。
+//!
+//! 通常は直接依存せず、`zsh-system` が再エクスポートする [`zsh_builtin`] を使用します。
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Expr, ExprLit, FnArg, GenericArgument, ItemFn, Lit, LitStr, Meta, MetaNameValue, Pat,
+    PathArguments, Type, parse_macro_input,
+};
+
+/// Rustの関数をZshのビルトインコマンドとして定義します。
+///
+/// 関数の引数はビルトインの引数から自動的に変換されます。
+///
+/// - 先頭の `&BuiltinCtx` / `&mut BuiltinCtx` には実行時コンテキストが渡されます (省略可能)。
+/// - `FromBuiltinArg` を実装した型 (`String`, `u32`, `PathBuf` など) は必須の引数になります。
+/// - `Option<T>` は省略可能な引数になります。必須の引数の後に置く必要があります。
+/// - `Vec<T>` は残りの引数すべてを受け取ります。最後に置く必要があります。
+///
+/// 引数の数は関数のシグネチャから決まります。引数の数が範囲外の場合や変換に失敗した場合は
+/// エラーと生成された使い方 (`usage: ...`) を表示し、終了ステータス `1` を返します。
+/// 関数のドキュメントコメントはヘルプの説明として使用され、`name --help`
+/// (オプション文字列を宣言した場合は `name -h`) で表示されます。
+///
+/// 定義したビルトインはモジュールのロード時に登録され、`export_module!` によって
+/// `ZshModule::features` の結果に自動的に追加されます。
+/// 登録にはロード時に実行される初期化関数 (ELFの `.init_array`、Mach-Oの
+/// `__DATA,__mod_init_func`) を使用するため、それ以外の形式のターゲットでは使用できません。
+///
+/// # 属性
+/// - `name = "..."`: コマンド名。省略した場合は関数名を使用します。
+/// - `opts = "..."`: Zshのビルトインと同じ形式のオプション文字列 (例: `"lv:"`)。
+///   `-h` はヘルプの表示に使用されるため、ドキュメントコメントや `help(...)` と共に
+///   `h` を宣言することはできません。
+/// - `help(v = "...", ...)`: ヘルプに表示する各オプションの説明。
+///
+/// ```ignore
+/// use std::path::PathBuf;
+/// use zsh_system::{BuiltinCtx, zsh_builtin};
+///
+/// /// Copy the first `count` lines to `dest`.
+/// #[zsh_builtin(name = "copy-n", opts = "v", help(v = "print each copied line"))]
+/// fn copy_n(ctx: &BuiltinCtx, count: u32, dest: PathBuf, label: Option<String>) -> i32 {
+///     if ctx.options().is_set('v') {
+///         // ...
+///     }
+///     0
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn zsh_builtin(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name: Option<LitStr> = None;
+    let mut opts: Option<LitStr> = None;
+    let mut option_help: Vec<(char, LitStr)> = Vec::new();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("opts") {
+            opts = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("help") {
+            meta.parse_nested_meta(|nested| {
+                let option = nested
+                    .path
+                    .get_ident()
+                    .map(|i| i.to_string())
+                    .filter(|i| i.chars().count() == 1)
+                    .ok_or_else(|| nested.error("expected a single option character"))?;
+                let c = option.chars().next().unwrap_or_default();
+                option_help.push((c, nested.value()?.parse()?));
+                Ok(())
+            })
+        } else {
+            Err(meta.error("unsupported zsh_builtin property, expected `name`, `opts` or `help`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let func = parse_macro_input!(item as ItemFn);
+
+    match expand(name, opts, option_help, func) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// 関数の引数がビルトインの引数をどのように受け取るか。
+enum Param {
+    /// 実行時コンテキスト (`&BuiltinCtx`)。
+    Ctx,
+    /// 必須の引数。
+    Required(String, Type),
+    /// 省略可能な引数 (`Option<T>`)。
+    Optional(String, Type),
+    /// 残りの引数 (`Vec<T>`)。
+    Rest(String, Type),
+}
+
+fn expand(
+    name: Option<LitStr>,
+    opts: Option<LitStr>,
+    option_help: Vec<(char, LitStr)>,
+    func: ItemFn,
+) -> syn::Result<TokenStream2> {
+    let sig = &func.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "zsh_builtin functions cannot be generic",
+        ));
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "zsh_builtin functions cannot be async",
+        ));
+    }
+
+    let fn_ident = &sig.ident;
+    let name = name.map_or_else(|| fn_ident.to_string(), |n| n.value());
+    let opts = opts.map(|o| o.value());
+
+    let params = sig
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| classify(i, input))
+        .collect::<syn::Result<Vec<_>>>()?;
+    check_order(&params, sig)?;
+
+    let description = description(&func);
+    let declares_h = opts.as_deref().is_some_and(|o| o.contains('h'));
+    if declares_h && (description.is_some() || !option_help.is_empty()) {
+        return Err(syn::Error::new(
+            sig.ident.span(),
+            "`-h` is reserved for help; remove `h` from `opts` or the doc comment and `help(...)`",
+        ));
+    }
+    let mut bindings = Vec::new();
+    let mut call_args = Vec::new();
+    let mut arg_decls = Vec::new();
+    let mut index = 0usize;
+    let mut min_args = 0u32;
+    let mut max_args = Some(0u32);
+    for (i, param) in params.iter().enumerate() {
+        let var = format_ident!("__arg{}", i);
+        let (convert, pname, ty) = match param {
+            Param::Ctx => {
+                call_args.push(quote!(__ctx));
+                continue;
+            }
+            Param::Required(pname, ty) => {
+                min_args += 1;
+                (quote!(convert_arg), pname, quote!(#ty))
+            }
+            Param::Optional(pname, ty) => {
+                (quote!(convert_optional_arg), pname, quote!(Option<#ty>))
+            }
+            Param::Rest(pname, ty) => {
+                max_args = None;
+                (quote!(convert_rest_args), pname, quote!(Vec<#ty>))
+            }
+        };
+        max_args = max_args.map(|m| m + 1);
+        let completion = completion(param);
+        arg_decls.push(match param {
+            Param::Rest(..) => quote!(.rest(#pname, #completion)),
+            _ => quote!(.arg(#pname, #completion)),
+        });
+        bindings.push(quote! {
+            let #var: #ty = match ::zsh_system::__private_api::#convert(__ctx, #pname, #index) {
+                Ok(value) => value,
+                Err(status) => return status,
+            };
+        });
+        call_args.push(quote!(#var));
+        index += 1;
+    }
+
+    let optstr = opts.map(|o| quote!(.optstr(#o)));
+    let max_args = max_args.map(|m| quote!(.max_args(#m)));
+    let description = description.map(|d| quote!(.description(#d)));
+    let option_help = option_help
+        .iter()
+        .map(|(c, help)| quote!(.option_help(#c, #help)));
+    let spec_fn = format_ident!("__zsh_builtin_spec_{}", fn_ident);
+    let init = format_ident!("__zsh_builtin_init_{}", fn_ident);
+
+    Ok(quote! {
+        #func
+
+        #[doc(hidden)]
+        fn #spec_fn() -> ::zsh_system::BuiltinSpec {
+            ::zsh_system::BuiltinSpec::closure(#name, |__ctx: &mut ::zsh_system::BuiltinCtx| -> i32 {
+                #(#bindings)*
+                #fn_ident(#(#call_args),*)
+            })
+            #optstr
+            .min_args(#min_args)
+            #max_args
+            #(#arg_decls)*
+            #description
+            #(#option_help)*
+        }
+
+        // モジュールのロード時 (dlopen) に実行され、ビルトインの定義を登録する。
+        // 初期化関数を置くセクションはオブジェクト形式 (ELF / Mach-O) ごとに異なる
+        #[cfg(not(unix))]
+        ::core::compile_error!("#[zsh_builtin] is only supported on ELF and Mach-O targets");
+
+        #[doc(hidden)]
+        #[used]
+        #[allow(non_upper_case_globals)]
+        #[cfg_attr(
+            all(unix, not(target_vendor = "apple")),
+            unsafe(link_section = ".init_array")
+        )]
+        #[cfg_attr(target_vendor = "apple", unsafe(link_section = "__DATA,__mod_init_func"))]
+        static #init: extern "C" fn() = {
+            extern "C" fn __init() {
+                ::zsh_system::__private_api::submit_builtin(#spec_fn);
+            }
+            __init
+        };
+    })
+}
+
+/// 関数の引数を分類します。
+fn classify(position: usize, input: &FnArg) -> syn::Result<Param> {
+    let typed = match input {
+        FnArg::Receiver(r) => {
+            return Err(syn::Error::new(
+                r.span(),
+                "zsh_builtin functions cannot take `self`",
+            ));
+        }
+        FnArg::Typed(typed) => typed,
+    };
+    if position == 0 && is_ctx(&typed.ty) {
+        return Ok(Param::Ctx);
+    }
+    let pname = match &*typed.pat {
+        Pat::Ident(ident) => ident.ident.to_string(),
+        other => {
+            return Err(syn::Error::new(
+                other.span(),
+                "zsh_builtin arguments must be plain identifiers",
+            ));
+        }
+    };
+    let pname = pname.trim_start_matches('_').to_string();
+    Ok(match wrapped_type(&typed.ty) {
+        Some(("Option", inner)) => Param::Optional(pname, inner),
+        Some(("Vec", inner)) => Param::Rest(pname, inner),
+        _ => Param::Required(pname, (*typed.ty).clone()),
+    })
+}
+
+/// 型が `&BuiltinCtx` / `&mut BuiltinCtx` (パス付きも含む) かどうかを返します。
+fn is_ctx(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+    let Type::Path(path) = &*reference.elem else {
+        return false;
+    };
+    path.path
+        .segments
+        .last()
+        .is_some_and(|s| s.ident == "BuiltinCtx")
+}
+
+/// `Option<T>` / `Vec<T>` の場合に、その名前と `T` を返します。
+fn wrapped_type(ty: &Type) -> Option<(&'static str, Type)> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let wrapper = match segment.ident.to_string().as_str() {
+        "Option" => "Option",
+        "Vec" => "Vec",
+        _ => return None,
+    };
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(inner)) if args.args.len() == 1 => {
+            Some((wrapper, inner.clone()))
+        }
+        _ => None,
+    }
+}
+
+/// 省略可能な引数の後に必須の引数が無いこと、残りの引数が最後にあることを検査します。
+fn check_order(params: &[Param], sig: &syn::Signature) -> syn::Result<()> {
+    let mut seen_optional = false;
+    let mut seen_rest = false;
+    for param in params {
+        if seen_rest && !matches!(param, Param::Ctx) {
+            return Err(syn::Error::new(
+                sig.inputs.span(),
+                "`Vec<T>` must be the last argument of a zsh_builtin function",
+            ));
+        }
+        match param {
+            Param::Required(..) if seen_optional => {
+                return Err(syn::Error::new(
+                    sig.inputs.span(),
+                    "required arguments must come before `Option<T>` arguments",
+                ));
+            }
+            Param::Optional(..) => seen_optional = true,
+            Param::Rest(..) => seen_rest = true,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// 引数の型から補完方法を決めます。パスは `Files`、文字列は `Default`、それ以外は説明のみです。
+fn completion(param: &Param) -> TokenStream2 {
+    let ty = match param {
+        Param::Required(_, ty) | Param::Optional(_, ty) | Param::Rest(_, ty) => ty,
+        Param::Ctx => return quote!(::zsh_system::ArgCompletion::Message),
+    };
+    let ident = match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+    match ident.as_deref() {
+        Some("PathBuf" | "OsString") => quote!(::zsh_system::ArgCompletion::Files),
+        Some("String") => quote!(::zsh_system::ArgCompletion::Default),
+        _ => quote!(::zsh_system::ArgCompletion::Message),
+    }
+}
+
+/// 関数のドキュメントコメントを連結して説明文を生成します。
+fn description(func: &ItemFn) -> Option<String> {
+    let lines: Vec<String> = func
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(s), ..
+                    }),
+                ..
+            }) => Some(s.value().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn expand_fn(func: ItemFn) -> syn::Result<String> {
+        expand(None, None, Vec::new(), func).map(|tokens| tokens.to_string())
+    }
+
+    fn expand_err(func: ItemFn) -> String {
+        match expand_fn(func) {
+            Ok(tokens) => panic!("expected an error, got: {}", tokens),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn derives_argument_counts_from_signature() {
+        let tokens = expand_fn(parse_quote! {
+            fn copy_n(ctx: &BuiltinCtx, count: u32, dest: PathBuf, label: Option<String>) -> i32 {
+                0
+            }
+        })
+        .unwrap();
+        assert!(tokens.contains("min_args (2u32)"), "{}", tokens);
+        assert!(tokens.contains("max_args (3u32)"), "{}", tokens);
+        assert!(
+            tokens.contains("copy_n (__ctx , __arg1 , __arg2 , __arg3)"),
+            "{}",
+            tokens
+        );
+    }
+
+    #[test]
+    fn rest_argument_has_no_maximum() {
+        let tokens = expand_fn(parse_quote! {
+            fn join(sep: String, words: Vec<String>) -> i32 {
+                0
+            }
+        })
+        .unwrap();
+        assert!(tokens.contains("min_args (1u32)"), "{}", tokens);
+        assert!(!tokens.contains("max_args"), "{}", tokens);
+        assert!(tokens.contains("convert_rest_args"), "{}", tokens);
+    }
+
+    #[test]
+    fn context_only_counts_when_it_is_builtin_ctx() {
+        let tokens = expand_fn(parse_quote! {
+            fn with_ctx(ctx: &mut zsh_system::BuiltinCtx) -> i32 {
+                0
+            }
+        })
+        .unwrap();
+        assert!(tokens.contains("min_args (0u32)"), "{}", tokens);
+        assert!(tokens.contains("max_args (0u32)"), "{}", tokens);
+
+        // `&str` は引数として扱われ、`FromBuiltinArg` の実装が無いことが報告される
+        let tokens = expand_fn(parse_quote! {
+            fn takes_str(s: &str) -> i32 {
+                0
+            }
+        })
+        .unwrap();
+        assert!(tokens.contains("min_args (1u32)"), "{}", tokens);
+        assert!(tokens.contains("convert_arg"), "{}", tokens);
+    }
+
+    #[test]
+    fn rejects_required_after_optional() {
+        let err = expand_err(parse_quote! {
+            fn f(a: Option<String>, b: u32) -> i32 {
+                0
+            }
+        });
+        assert!(
+            err.contains("required arguments must come before"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_rest_before_other_arguments() {
+        let err = expand_err(parse_quote! {
+            fn f(a: Vec<String>, b: u32) -> i32 {
+                0
+            }
+        });
+        assert!(err.contains("must be the last argument"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_generics() {
+        let err = expand_err(parse_quote! {
+            fn f<T: FromBuiltinArg>(a: T) -> i32 {
+                0
+            }
+        });
+        assert!(err.contains("cannot be generic"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_self() {
+        let err = expand_err(parse_quote! {
+            fn f(&self, a: u32) -> i32 {
+                0
+            }
+        });
+        assert!(err.contains("cannot take `self`"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_h_option_with_help() {
+        let opts: LitStr = parse_quote!("hv");
+        let result = expand(
+            None,
+            Some(opts),
+            Vec::new(),
+            parse_quote! {
+                /// Show something.
+                fn f() -> i32 {
+                    0
+                }
+            },
+        );
+        let err = result.map(|t| t.to_string()).unwrap_err().to_string();
+        assert!(err.contains("reserved for help"), "{}", err);
+    }
+}